@@ -0,0 +1,371 @@
+use std::vec;
+
+use super::case;
+use super::compile;
+use super::compile::{
+    Instruction,
+    Char,
+    Any,
+    Range,
+    Class,
+    Fork,
+    Jump,
+    ConditionalJumpEq,
+    ConditionalJumpLE,
+    Increment,
+    ResetRegister,
+    SavePosition,
+    AssertProgress,
+    SaveStart,
+    SaveEnd,
+    AssertStart,
+    AssertEnd,
+    AssertLineStart,
+    AssertLineEnd,
+    AssertWordBoundary,
+    AssertNonWordBoundary,
+    LookAhead,
+    NegativeLookAhead,
+    LookBehind,
+    NegativeLookBehind,
+    Backreference,
+    Accept
+};
+
+use super::matcher;
+use super::matcher::Matcher;
+
+// A saved alternative to resume from when the current path fails
+struct Job {
+    pc: uint,
+    sp: uint,
+    captures: ~[Option<matcher::Match>],
+    registers: ~[uint]
+}
+
+// A depth-first backtracking matcher. Slower than the Pike VM in the worst case,
+// but able to handle backreferences, since every path carries its own captures
+// in the order ECMA-262 prescribes.
+pub struct BacktrackMatcher<'self> {
+    priv code: &'self [Instruction],
+    priv jobs: ~[Job],
+    priv input: &'self [char],
+    priv registers: uint,
+    priv captures: uint,
+    priv reverse: bool, // Matches backwards from the start position, used for lookbehind
+    priv full: bool // Only accepts at the end of the input, for is_full_match
+}
+
+impl<'self> BacktrackMatcher<'self> {
+    // Finds the first match starting at or after start, or only at start if anchored,
+    // with the given number of capture slots
+    pub fn search<'r>(code: &'r [Instruction], input: &'r [char], captures: uint, start: uint, anchored: bool) -> Option<~[Option<matcher::Match>]> {
+        let mut matcher = BacktrackMatcher::new(code, input, compile::count_registers(code), captures, false);
+
+        return matcher.run(0, start, anchored);
+    }
+
+    // Whether the program matches the whole input
+    pub fn is_full_match<'r>(code: &'r [Instruction], input: &'r [char], captures: uint) -> bool {
+        let mut matcher = BacktrackMatcher::new(code, input, compile::count_registers(code), captures, false);
+        matcher.full = true;
+
+        return matcher.run(0, 0, true).is_some();
+    }
+
+    fn new<'r>(code: &'r [Instruction], input: &'r [char], registers: uint, captures: uint, reverse: bool) -> BacktrackMatcher<'r> {
+        BacktrackMatcher {
+            code: code,
+            jobs: ~[],
+            input: input,
+            registers: registers,
+            captures: captures,
+            reverse: reverse,
+            full: false
+        }
+    }
+
+    // The character that will be consumed next from sp, in the direction of the matcher
+    #[inline]
+    fn next_char(&self, sp: uint) -> Option<char> {
+        if self.reverse {
+            if sp > 0 { Some(self.input[sp - 1]) } else { None }
+        } else {
+            if sp < self.input.len() { Some(self.input[sp]) } else { None }
+        }
+    }
+
+    #[inline]
+    fn advance(&self, sp: uint, n: uint) -> uint {
+        if self.reverse { sp - n } else { sp + n }
+    }
+
+    #[inline]
+    fn is_word_boundary(&self, sp: uint, unicode: bool) -> bool {
+        let a = sp > 0 && matcher::is_word_char(self.input[sp - 1], unicode);
+        let b = sp < self.input.len() && matcher::is_word_char(self.input[sp], unicode);
+        a != b
+    }
+
+    #[inline]
+    fn look_around(&self, pc: uint, sp: uint, reverse: bool) -> Option<~[Option<matcher::Match>]> {
+        let mut matcher = BacktrackMatcher::new(self.code, self.input, self.registers, self.captures, reverse);
+        matcher.run(pc, sp, true)
+    }
+
+    // Returns the position after matching the text of the capture at sp. A
+    // group that did not participate in the match matches the empty string.
+    #[inline]
+    fn match_backreference(&self, capture: Option<matcher::Match>, sp: uint, fold: Option<case::FoldLevel>) -> Option<uint> {
+        match capture {
+            Some(m) => {
+                let len = m.end - m.start;
+                let start = if self.reverse {
+                    if sp < len { return None; }
+                    sp - len
+                } else {
+                    if sp + len > self.input.len() { return None; }
+                    sp
+                };
+
+                for i in range(0, len) {
+                    let (a, b) = (self.input[m.start + i], self.input[start + i]);
+                    if a != b {
+                        match fold {
+                            Some(level) if case::canonicalize(a, level) == case::canonicalize(b, level) => (),
+                            _ => return None
+                        }
+                    }
+                }
+
+                Some(self.advance(sp, len))
+            }
+            None => Some(sp)
+        }
+    }
+
+    // Follows a single path until it either fails or reaches Accept, saving
+    // alternatives on the job stack along the way.
+    fn run_job(&mut self, mut job: Job, match_start: uint) -> Option<~[Option<matcher::Match>]> {
+        loop {
+            match self.code[job.pc] {
+                Char(ch) => {
+                    match self.next_char(job.sp) {
+                        Some(c) if c == ch => {
+                            job.sp = self.advance(job.sp, 1);
+                            job.pc += 1;
+                        }
+                        _ => return None
+                    }
+                }
+                Any => {
+                    match self.next_char(job.sp) {
+                        Some(_) => {
+                            job.sp = self.advance(job.sp, 1);
+                            job.pc += 1;
+                        }
+                        None => return None
+                    }
+                }
+                Range(start, end) => {
+                    match self.next_char(job.sp) {
+                        Some(c) if c >= start && c <= end => {
+                            job.sp = self.advance(job.sp, 1);
+                            job.pc += 1;
+                        }
+                        _ => return None
+                    }
+                }
+                Class(ref class) => {
+                    match self.next_char(job.sp) {
+                        Some(c) if class.contains(c) => {
+                            job.sp = self.advance(job.sp, 1);
+                            job.pc += 1;
+                        }
+                        _ => return None
+                    }
+                }
+                Fork(pc1, pc2) => {
+                    self.jobs.push(
+                        Job {
+                            pc: pc2,
+                            sp: job.sp,
+                            captures: job.captures.clone(),
+                            registers: job.registers.clone() });
+
+                    job.pc = pc1;
+                }
+                Jump(new_pc) => job.pc = new_pc,
+                ConditionalJumpEq(register, value, new_pc) => {
+                    if job.registers[register] == value {
+                        job.pc = new_pc;
+                    } else {
+                        job.pc += 1;
+                    }
+                }
+                ConditionalJumpLE(register, value, new_pc) => {
+                    if job.registers[register] < value {
+                        job.pc = new_pc;
+                    } else {
+                        job.pc += 1;
+                    }
+                }
+                Increment(register) => {
+                    job.registers[register] += 1;
+                    job.pc += 1;
+                }
+                ResetRegister(register) => {
+                    job.registers[register] = 0;
+                    job.pc += 1;
+                }
+                SavePosition(register) => {
+                    job.registers[register] = job.sp;
+                    job.pc += 1;
+                }
+                // An optional loop iteration that matched the empty string
+                AssertProgress(register) => {
+                    if job.registers[register] == job.sp {
+                        return None;
+                    } else {
+                        job.pc += 1;
+                    }
+                }
+                SaveStart(group) => {
+                    job.captures[group] = Some(matcher::Match{ start: job.sp, end: job.sp });
+
+                    job.pc += 1;
+                }
+                SaveEnd(group) => {
+                    match job.captures[group] {
+                        Some(ref mut m) => {
+                            // When matching backwards the end was saved first
+                            if self.reverse {
+                                m.start = job.sp;
+                            } else {
+                                m.end = job.sp;
+                            }
+                        }
+                        None => unreachable!()
+                    }
+
+                    job.pc += 1;
+                }
+                AssertStart => {
+                    if job.sp == 0 {
+                        job.pc += 1;
+                    } else {
+                        return None;
+                    }
+                }
+                AssertEnd => {
+                    if job.sp == self.input.len() {
+                        job.pc += 1;
+                    } else {
+                        return None;
+                    }
+                }
+                AssertLineStart => {
+                    if job.sp == 0 || matcher::is_line_terminator(self.input[job.sp - 1]) {
+                        job.pc += 1;
+                    } else {
+                        return None;
+                    }
+                }
+                AssertLineEnd => {
+                    if job.sp == self.input.len() || matcher::is_line_terminator(self.input[job.sp]) {
+                        job.pc += 1;
+                    } else {
+                        return None;
+                    }
+                }
+                AssertWordBoundary(unicode) => {
+                    if self.is_word_boundary(job.sp, unicode) {
+                        job.pc += 1;
+                    } else {
+                        return None;
+                    }
+                }
+                AssertNonWordBoundary(unicode) => {
+                    if self.is_word_boundary(job.sp, unicode) {
+                        return None;
+                    } else {
+                        job.pc += 1;
+                    }
+                }
+                LookAhead(next_pc) |
+                LookBehind(next_pc) => {
+                    let reverse = match self.code[job.pc] { LookBehind(_) => true, _ => false };
+                    match self.look_around(job.pc + 1, job.sp, reverse) {
+                        Some(captures) => {
+                            // Captures made inside the assertion are kept
+                            for (group, capture) in captures.iter().enumerate().skip(1) {
+                                if capture.is_some() {
+                                    job.captures[group] = *capture;
+                                }
+                            }
+                            job.pc = next_pc;
+                        }
+                        None => return None
+                    }
+                }
+                NegativeLookAhead(next_pc) |
+                NegativeLookBehind(next_pc) => {
+                    let reverse = match self.code[job.pc] { NegativeLookBehind(_) => true, _ => false };
+                    match self.look_around(job.pc + 1, job.sp, reverse) {
+                        Some(_) => return None,
+                        None => job.pc = next_pc
+                    }
+                }
+                Backreference(group, fold) => {
+                    match self.match_backreference(job.captures[group], job.sp, fold) {
+                        Some(sp) => {
+                            job.sp = sp;
+                            job.pc += 1;
+                        }
+                        None => return None
+                    }
+                }
+                Accept if self.full && job.sp != self.input.len() => return None,
+                Accept => {
+                    let mut captures = job.captures.clone();
+                    captures[0] = if self.reverse {
+                        Some(matcher::Match{ start: job.sp, end: match_start })
+                    } else {
+                        Some(matcher::Match{ start: match_start, end: job.sp })
+                    };
+                    return Some(captures);
+                }
+            }
+        }
+    }
+}
+
+impl<'self> Matcher for BacktrackMatcher<'self> {
+    fn run(&mut self, start_pc: uint, start_sp: uint, anchored: bool) -> Option<~[Option<matcher::Match>]> {
+        let mut sp = start_sp;
+
+        loop {
+            self.jobs.clear();
+            self.jobs.push(
+                Job {
+                    pc: start_pc,
+                    sp: sp,
+                    captures: vec::from_elem(self.captures, None),
+                    registers: vec::from_elem(self.registers, 0u) });
+
+            // The first path to reach Accept is the one with the highest priority
+            while !self.jobs.is_empty() {
+                let job = self.jobs.pop();
+                match self.run_job(job, sp) {
+                    Some(captures) => return Some(captures),
+                    None => ()
+                }
+            }
+
+            if anchored || self.next_char(sp).is_none() {
+                return None;
+            }
+            sp = self.advance(sp, 1);
+        }
+    }
+}
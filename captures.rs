@@ -0,0 +1,244 @@
+use std::str;
+use std::from_str;
+use std::hashmap::HashMap;
+
+use super::matcher;
+
+// A matched part of the text, with byte offsets that can be used to slice it
+#[deriving(Clone)]
+pub struct Match<'self> {
+    priv text: &'self str,
+    priv start: uint,
+    priv end: uint
+}
+
+impl<'self> Match<'self> {
+    pub fn new<'r>(text: &'r str, start: uint, end: uint) -> Match<'r> {
+        Match { text: text, start: start, end: end }
+    }
+
+    // Byte offset of the start of the match
+    pub fn start(&self) -> uint {
+        self.start
+    }
+
+    // Byte offset just past the end of the match
+    pub fn end(&self) -> uint {
+        self.end
+    }
+
+    pub fn range(&self) -> (uint, uint) {
+        (self.start, self.end)
+    }
+
+    pub fn as_str(&self) -> &'self str {
+        self.text.slice(self.start, self.end)
+    }
+
+    // The offsets in characters instead of bytes. Takes time linear in the
+    // position of the match, so it's only computed on request.
+    pub fn char_range(&self) -> (uint, uint) {
+        let start = self.text.slice_to(self.start).char_len();
+        (start, start + self.as_str().char_len())
+    }
+}
+
+// The byte offset of every character index in the text, including the end
+pub fn byte_offsets(text: &str) -> ~[uint] {
+    let mut offsets: ~[uint] = text.char_offset_iter().map(|(i, _)| i).collect();
+    offsets.push(text.len());
+    return offsets;
+}
+
+// The groups of a match, where group N is always at index N and the full
+// match at 0. Groups that did not participate in the match are None, like
+// undefined in http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.1
+pub struct Captures<'self> {
+    priv text: &'self str,
+    priv groups: ~[Option<(uint, uint)>], // Byte offsets
+    priv names: &'self HashMap<~str, uint>
+}
+
+impl<'self> Captures<'self> {
+    // Converts the character indices the matchers work with to byte offsets,
+    // using the offsets from byte_offsets
+    pub fn new<'r>(text: &'r str, offsets: &[uint], groups: &[Option<matcher::Match>], names: &'r HashMap<~str, uint>) -> Captures<'r> {
+        Captures {
+            text: text,
+            groups: groups.map(|group| {
+                match *group {
+                    Some(m) => Some((offsets[m.start], offsets[m.end])),
+                    None => None
+                }
+            }),
+            names: names
+        }
+    }
+
+    // The number of groups, including the full match
+    pub fn len(&self) -> uint {
+        self.groups.len()
+    }
+
+    // The match of the group, or None if it didn't match or doesn't exist
+    pub fn get(&self, group: uint) -> Option<Match<'self>> {
+        if group >= self.groups.len() {
+            return None;
+        }
+
+        match self.groups[group] {
+            Some((start, end)) => Some(Match { text: self.text, start: start, end: end }),
+            None => None
+        }
+    }
+
+    // The match of the named group, or None if it didn't match or doesn't exist
+    pub fn name(&self, name: &str) -> Option<Match<'self>> {
+        match self.names.find_equiv(&name) {
+            Some(&group) => self.get(group),
+            None => None
+        }
+    }
+
+    // Every group in order, including the unmatched ones
+    pub fn iter<'r>(&'r self) -> SubCaptures<'r> {
+        SubCaptures { captures: self, group: 0 }
+    }
+
+    // Expands a replacement template like GetSubstitution in
+    // http://www.ecma-international.org/ecma-262/6.0/#sec-getsubstitution
+    // $$ is a $, $& the match, $` the text before it, $' the text after it,
+    // $n or $nn a group and ${name} a named group. Unmatched groups are empty,
+    // and a $ that doesn't start any of these is kept as it is.
+    pub fn expand(&self, template: &str) -> ~str {
+        let chars: ~[char] = template.iter().collect();
+        let (start, end) = self.groups[0].unwrap();
+        let mut result = ~"";
+        let mut i = 0;
+
+        while i < chars.len() {
+            if chars[i] != '$' || i + 1 == chars.len() {
+                result.push_char(chars[i]);
+                i += 1;
+                continue;
+            }
+
+            match chars[i + 1] {
+                '$' => {
+                    result.push_char('$');
+                    i += 2;
+                }
+                '&' => {
+                    result.push_str(self.text.slice(start, end));
+                    i += 2;
+                }
+                '`' => {
+                    result.push_str(self.text.slice_to(start));
+                    i += 2;
+                }
+                '\'' => {
+                    result.push_str(self.text.slice_from(end));
+                    i += 2;
+                }
+                '{' => {
+                    let close = range(i + 2, chars.len()).find(|&j| chars[j] == '}');
+                    let group = match close {
+                        Some(j) => self.group_index(str::from_chars(chars.slice(i + 2, j))),
+                        None => None
+                    };
+                    match (group, close) {
+                        (Some(group), Some(j)) => {
+                            result.push_str(self.group_str(group));
+                            i = j + 1;
+                        }
+                        _ => {
+                            result.push_char('$');
+                            i += 1;
+                        }
+                    }
+                }
+                c if c >= '0' && c <= '9' => {
+                    // Two digits are preferred if there is such a group
+                    let one = digit_value(c);
+                    let two = if i + 2 < chars.len() && chars[i + 2] >= '0' && chars[i + 2] <= '9' {
+                        Some(one * 10 + digit_value(chars[i + 2]))
+                    } else {
+                        None
+                    };
+
+                    match two {
+                        Some(n) if n > 0 && n < self.groups.len() => {
+                            result.push_str(self.group_str(n));
+                            i += 3;
+                        }
+                        _ if one > 0 && one < self.groups.len() => {
+                            result.push_str(self.group_str(one));
+                            i += 2;
+                        }
+                        _ => {
+                            result.push_char('$');
+                            i += 1;
+                        }
+                    }
+                }
+                _ => {
+                    result.push_char('$');
+                    i += 1;
+                }
+            }
+        }
+
+        return result;
+    }
+
+    // The group with the name or number
+    fn group_index(&self, name: &str) -> Option<uint> {
+        match self.names.find_equiv(&name) {
+            Some(&group) => Some(group),
+            None => match from_str::from_str(name) {
+                Some(group) if group < self.groups.len() => Some(group),
+                _ => None
+            }
+        }
+    }
+
+    // The text of the group, empty if it didn't match
+    fn group_str(&self, group: uint) -> &'self str {
+        match self.get(group) {
+            Some(m) => m.as_str(),
+            None => ""
+        }
+    }
+}
+
+// The text of the group. Fails if the group didn't match, use get to check.
+impl<'self> Index<uint, &'self str> for Captures<'self> {
+    fn index(&self, group: &uint) -> &'self str {
+        match self.get(*group) {
+            Some(m) => m.as_str(),
+            None => fail!("Group {} did not match", *group)
+        }
+    }
+}
+
+pub struct SubCaptures<'self> {
+    priv captures: &'self Captures<'self>,
+    priv group: uint
+}
+
+impl<'self> Iterator<Option<Match<'self>>> for SubCaptures<'self> {
+    fn next(&mut self) -> Option<Option<Match<'self>>> {
+        if self.group >= self.captures.len() {
+            return None;
+        }
+
+        let m = self.captures.get(self.group);
+        self.group += 1;
+        Some(m)
+    }
+}
+
+#[inline]
+fn digit_value(c: char) -> uint {
+    c as uint - '0' as uint
+}
@@ -0,0 +1,68 @@
+use extra::sort;
+
+use super::unicode;
+use super::charclass::CharClass;
+
+// How characters are compared when ignoring case
+#[deriving(Eq, Clone)]
+pub enum FoldLevel {
+    EcmaFold, // Canonicalize from ECMA-262, the default
+    SimpleFold, // Unicode simple case folding, where each character folds to one character
+    FullFold // Also folds characters like ß to sequences like ss, for literals outside of classes
+}
+
+// Two characters match when ignoring case if their canonical forms are equal.
+// By default this is Canonicalize from
+// http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.8, the upper
+// case of the character, unless that turns a non-ASCII character into an
+// ASCII one, like \u017f into S. The Unicode levels use the simple case
+// folding, since a single character can't be compared to a sequence.
+pub fn canonicalize(c: char, level: FoldLevel) -> char {
+    match level {
+        EcmaFold => {
+            let upper = unicode::to_upper(c);
+            if is_canonical(c, upper, level) { upper } else { c }
+        }
+        SimpleFold | FullFold => unicode::simple_fold(c)
+    }
+}
+
+// Extends the ranges with every character that has the same canonical form as
+// a character already in them.
+pub fn fold_ranges(ranges: &[(char, char)], level: FoldLevel) -> ~[(char, char)] {
+    let mut folded = ranges.to_owned();
+    let mappings = match level {
+        EcmaFold => unicode::upper_mappings(),
+        SimpleFold | FullFold => unicode::simple_folds()
+    };
+
+    // The canonical forms of every character in the ranges that has one other
+    // than itself, or is one. The class merges the ranges, so each lookup is
+    // a binary search.
+    let class = CharClass::new(ranges);
+    let mut targets = ~[];
+    for &(from, to) in mappings.iter() {
+        if is_canonical(from, to, level) && (class.contains(from) || class.contains(to)) {
+            targets.push(to);
+        }
+    }
+    let mut targets = sort::merge_sort(targets, |a, b| a <= b);
+    targets.dedup();
+
+    for &(from, to) in mappings.iter() {
+        if is_canonical(from, to, level) && targets.bsearch_elem(&to).is_some() {
+            folded.push((from, from));
+        }
+    }
+    for &c in targets.iter() {
+        folded.push((c, c));
+    }
+
+    return folded;
+}
+
+// Whether to, from the mappings of the level, is the canonical form of from
+#[inline]
+fn is_canonical(from: char, to: char, level: FoldLevel) -> bool {
+    level != EcmaFold || (from as u32) < 128 || (to as u32) >= 128
+}
@@ -0,0 +1,75 @@
+use extra::sort;
+
+// The characters matched by a Class instruction. The ranges are sorted and
+// merged, so a character is looked up with a binary search, and ASCII
+// characters with a bitmap.
+pub struct CharClass {
+    priv ranges: ~[(char, char)],
+    priv ascii: [u64, ..2] // Bit c is set if ASCII character c is in the class
+}
+
+impl CharClass {
+    pub fn new(ranges: &[(char, char)]) -> CharClass {
+        let sorted = sort::merge_sort(ranges, |a, b| a.first() <= b.first());
+
+        // Overlapping and adjacent ranges are merged
+        let mut merged: ~[(char, char)] = ~[];
+        for &(start, end) in sorted.iter() {
+            let last = merged.len();
+            if last > 0 {
+                let (s, e) = merged[last - 1];
+                if start as u32 <= e as u32 + 1 {
+                    if end > e {
+                        merged[last - 1] = (s, end);
+                    }
+                    continue;
+                }
+            }
+            merged.push((start, end));
+        }
+
+        let mut ascii = [0u64, 0u64];
+        for &(start, end) in merged.iter() {
+            let mut c = start as u64;
+            while c <= end as u64 && c < 128 {
+                ascii[(c / 64) as uint] |= 1u64 << (c % 64);
+                c += 1;
+            }
+        }
+
+        CharClass { ranges: merged, ascii: ascii }
+    }
+
+    #[inline]
+    pub fn contains(&self, c: char) -> bool {
+        let n = c as u64;
+        if n < 128 {
+            return self.ascii[(n / 64) as uint] & (1u64 << (n % 64)) != 0;
+        }
+
+        let (mut low, mut high) = (0, self.ranges.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            let (start, end) = self.ranges[mid];
+            if c < start {
+                high = mid;
+            } else if c > end {
+                low = mid + 1;
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+
+    // The sorted and merged ranges
+    pub fn ranges<'r>(&'r self) -> &'r [(char, char)] {
+        self.ranges.as_slice()
+    }
+}
+
+impl Clone for CharClass {
+    fn clone(&self) -> CharClass {
+        CharClass { ranges: self.ranges.clone(), ascii: self.ascii }
+    }
+}
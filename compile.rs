@@ -1,9 +1,20 @@
+use std::uint;
+use std::vec;
+
 use EAssertStart = super::parse::AssertStart;
 use EAssertEnd = super::parse::AssertEnd;
+use EAssertLineStart = super::parse::AssertLineStart;
+use EAssertLineEnd = super::parse::AssertLineEnd;
 use EAssertWordBoundary = super::parse::AssertWordBoundary;
 use EAssertNonWordBoundary = super::parse::AssertNonWordBoundary;
+use ELookAhead = super::parse::LookAhead;
+use ENegativeLookAhead = super::parse::NegativeLookAhead;
+use ELookBehind = super::parse::LookBehind;
+use ENegativeLookBehind = super::parse::NegativeLookBehind;
+use EBackreference = super::parse::Backreference;
 use super::parse::{
 	Expression,
+	Empty,
 	Literal,
 	AnyLiteral,
 	RangeLiteral,
@@ -16,70 +27,134 @@ use super::parse::{
 	Plus,
 	ExactRepetition,
 	UnboundedRepetition,
-	BoundedRepetition
+	BoundedRepetition,
+	NamedBackreference
 };
+use super::case::FoldLevel;
+use super::charclass::CharClass;
 use super::parse::{
 	QuantifierType,
 	Greedy,
 	NonGreedy
 };
 
+#[deriving(Clone)]
 pub enum Instruction {
     Char(char),
     Range(char, char),
+    Class(~CharClass), // Any character in the class
     Any,
     Fork(uint, uint),
     Jump(uint), 
     ConditionalJumpEq(uint, uint, uint), // (register, value, pc)
     ConditionalJumpLE(uint, uint, uint), // (register, value, pc)
     Increment(uint),
+    ResetRegister(uint), // Sets the register to zero when a counted loop is entered
+    SavePosition(uint), // (register) Stores the current position at the start of a loop iteration
+    AssertProgress(uint), // (register) Fails if the position still equals the saved one
     SaveStart(uint),
     SaveEnd(uint),
     AssertStart,
     AssertEnd,
-    AssertWordBoundary,
-    AssertNonWordBoundary,
+    AssertLineStart, // Start of input or after a line terminator
+    AssertLineEnd, // End of input or before a line terminator
+    AssertWordBoundary(bool), // (unicode)
+    AssertNonWordBoundary(bool), // (unicode)
+    LookAhead(uint), // Sub program follows, terminated by Accept. Continues at pc
+    NegativeLookAhead(uint), // Sub program follows, terminated by Accept. Continues at pc
+    LookBehind(uint), // Reversed sub program follows, terminated by Accept. Continues at pc
+    NegativeLookBehind(uint), // Reversed sub program follows, terminated by Accept. Continues at pc
+    Backreference(uint, Option<FoldLevel>), // (group, case folding) Not supported by the Pike VM
     Accept
 }
 
-// TODO: Also defined in parse
-#[inline]
-fn do_alternate(stack: &mut ~[Expression]) {
-    while stack.len() > 1 {
-        let (right, left) = (stack.pop(), stack.pop());
-        stack.push(Alternate(~left, ~right));
+// The default for the largest number of instructions a counted repetition is
+// unrolled into
+pub static DEFAULT_UNROLL_LIMIT: uint = 1000;
+
+// State shared by the whole compilation
+struct Compiler {
+    registers: uint, // The number of registers allocated so far
+    unroll_limit: uint // The most instructions a counted repetition is unrolled into
+}
+
+impl Compiler {
+    fn new(unroll_limit: uint) -> Compiler {
+        Compiler { registers: 0, unroll_limit: unroll_limit }
     }
 }
 
-pub fn compile(expression: &Expression) -> ~[Instruction] {
+// Counted repetitions that fit in unroll_limit instructions are compiled to
+// copies of their expression. Larger ones use registers to count the
+// iterations, which makes threads at the same pc distinct, so the Pike VM can
+// no longer bound their number by the size of the program.
+pub fn compile(expression: &Expression, unroll_limit: uint) -> ~[Instruction] {
     let mut code = ~[];
-    compile_recursive(expression, &mut code, &mut 0);
+    compile_recursive(expression, &mut code, &mut Compiler::new(unroll_limit), false);
     code.push(Accept);
     return code;
 }
 
+// The number of registers the program uses, 0 if it needs none
 pub fn count_registers(code: &[Instruction]) -> uint {
-	let mut max = 0;
+	let mut count = 0;
 
 	for instruction in code.iter() {
 		match *instruction {
 			Increment(register) |
+			ResetRegister(register) |
+			SavePosition(register) |
+			AssertProgress(register) |
 			ConditionalJumpEq(register, _, _) |
 			ConditionalJumpLE(register, _, _) => {
-				if register > max {
-					max = register;
+				if register + 1 > count {
+					count = register + 1;
 				}
 			},
 			_ => ()
 		}
 	}
 
-	return max + 1;
+	return count;
+}
+
+// What a register holds, as far as it decides the future of a thread
+pub enum RegisterKind {
+	Counter(uint), // Iterations, only compared to numbers below the limit
+	Position // Saved by SavePosition, only compared to later positions
 }
 
-fn compile_recursive(expression: &Expression, code: &mut ~[Instruction], registers: &mut uint) {
+pub fn register_kinds(code: &[Instruction]) -> ~[RegisterKind] {
+	let mut kinds = vec::from_elem(count_registers(code), Counter(0));
+
+	for instruction in code.iter() {
+		match *instruction {
+			SavePosition(register) |
+			AssertProgress(register) => kinds[register] = Position,
+			// A count of uint::max_value can't be reached anyway
+			ConditionalJumpEq(register, value, _) if value < uint::max_value => raise_limit(&mut kinds[register], value + 1),
+			ConditionalJumpEq(register, value, _) => raise_limit(&mut kinds[register], value),
+			ConditionalJumpLE(register, value, _) => raise_limit(&mut kinds[register], value),
+			_ => ()
+		}
+	}
+
+	return kinds;
+}
+
+#[inline]
+fn raise_limit(kind: &mut RegisterKind, limit: uint) {
+	match *kind {
+		Counter(l) if l < limit => *kind = Counter(limit),
+		_ => ()
+	}
+}
+
+// If reverse is set, the code is compiled to match backwards, which is used for lookbehind
+fn compile_recursive(expression: &Expression, code: &mut ~[Instruction], compiler: &mut Compiler, reverse: bool) {
 
     match *expression {
+        Empty => (),
         Literal(c) => {
         	code.push(Char(c));
         }
@@ -89,101 +164,301 @@ fn compile_recursive(expression: &Expression, code: &mut ~[Instruction], registe
         RangeLiteral(start, end) => {
         	code.push(Range(start, end));
         }
-        CharacterClass(ref ranges) => {
-            let mut stack = ranges.map(|r| {
-                match r {
-                    &(start, end) if start == end => Literal(start),
-                    &(start, end) => RangeLiteral(start, end)
-                }
-            });
-            do_alternate(&mut stack);
-            compile_recursive(&stack.pop(), code, registers);
+        CharacterClass(ref ranges) if ranges.len() == 1 => {
+            match ranges[0] {
+                (start, end) if start == end => code.push(Char(start)),
+                (start, end) => code.push(Range(start, end))
+            }
         }
+        // A single instruction, however many ranges there are. An empty
+        // class matches nothing, like (?!)
+        CharacterClass(ref ranges) => code.push(Class(~CharClass::new(*ranges))),
         Concatenate(ref left, ref right) => {
-            compile_recursive(*left, code, registers);
-            compile_recursive(*right, code, registers);
+            if reverse {
+                compile_recursive(*right, code, compiler, reverse);
+                compile_recursive(*left, code, compiler, reverse);
+            } else {
+                compile_recursive(*left, code, compiler, reverse);
+                compile_recursive(*right, code, compiler, reverse);
+            }
         }
         Alternate(ref left, ref right) => {
             let pc = code.len();
             code.push(Fork(0, 0));
-            compile_recursive(*left, code, registers);
+            compile_recursive(*left, code, compiler, reverse);
             code[pc] = Fork(pc + 1, code.len() + 1);
             let jump_pc = code.len();
             code.push(Jump(0));
-            compile_recursive(*right, code, registers);
+            compile_recursive(*right, code, compiler, reverse);
             code[jump_pc] = Jump(code.len());
         }
-        SubExpression(ref e, capture_slot) => {
+        SubExpression(ref e, capture_slot, _) => {
         	match capture_slot {
         		Some(slot) => {
 		        	code.push(SaveStart(slot));
-		        	compile_recursive(*e, code, registers);
+		        	compile_recursive(*e, code, compiler, reverse);
 		        	code.push(SaveEnd(slot));
         		}
-        		None => compile_recursive(*e, code, registers)
+        		None => compile_recursive(*e, code, compiler, reverse)
         	}
         }
         Question(ref e, typ) => {
             let pc = code.len();
             code.push(Fork(0, 0));
-            compile_recursive(*e, code, registers);
+            compile_recursive(*e, code, compiler, reverse);
             let instr = fork(typ, pc + 1, code.len()); 
             code[pc] = instr;
         }
         Star(ref e, typ) => {
-            let pc = code.len();
-            code.push(Fork(0, 0));
-            compile_recursive(*e, code, registers);
-            let instr = fork(typ, pc + 1, code.len() + 1); 
-            code[pc] = instr;
-            code.push(Jump(pc));
+            let body = compile_body(*e, compiler, reverse);
+            compile_star(body, can_match_empty(*e), typ, code, compiler);
+        }
+        // The first iteration may match the empty string, so a nullable body
+        // is compiled like {1,} to only check the following ones
+        Plus(ref e, typ) if can_match_empty(*e) => {
+            let body = compile_body(*e, compiler, reverse);
+            compile_counted(body, true, 1, None, typ, code, compiler);
         }
         Plus(ref e, typ) => {
             let pc = code.len();
-            compile_recursive(*e, code, registers);
+            compile_recursive(*e, code, compiler, reverse);
             let instr = fork(typ, pc, code.len() + 1); 
             code.push(instr);
         }
         ExactRepetition(ref e, count, typ) => {
-        	let register = *registers;
-        	*registers += 1;
-            let pc = code.len();
-            code.push(ConditionalJumpEq(0, 0, 0));
-            code.push(Fork(0, 0));
-            compile_recursive(*e, code, registers);
-            code.push(Increment(register));
-            code[pc] = ConditionalJumpEq(register, count, code.len() + 1);
-            code[pc + 1] = fork(typ, pc + 2, code.len() + 1);
-            code.push(Jump(pc));
+            compile_repetition(*e, count, Some(count), typ, code, compiler, reverse);
         }
         UnboundedRepetition(ref e, low, typ) => {
-        	let register = *registers;
-        	*registers += 1;
-            let pc = code.len();
-            code.push(ConditionalJumpLE(register, low, pc + 2));
-            code.push(Fork(0, 0));
-            compile_recursive(*e, code, registers);
-            code.push(Increment(register));
-            code[pc + 1] = fork(typ, pc + 2, code.len() + 1);
-            code.push(Jump(pc));
+            compile_repetition(*e, low, None, typ, code, compiler, reverse);
         }
         BoundedRepetition(ref e, low, high, typ) => {
-        	let register = *registers;
-        	*registers += 1;
+            compile_repetition(*e, low, Some(high), typ, code, compiler, reverse);
+        }
+        EAssertStart => code.push(AssertStart),
+        EAssertEnd => code.push(AssertEnd),
+        EAssertLineStart => code.push(AssertLineStart),
+        EAssertLineEnd => code.push(AssertLineEnd),
+        EAssertWordBoundary(unicode) => code.push(AssertWordBoundary(unicode)),
+        EAssertNonWordBoundary(unicode) => code.push(AssertNonWordBoundary(unicode)),
+        ELookAhead(ref e) => {
             let pc = code.len();
+            code.push(LookAhead(0));
+            compile_recursive(*e, code, compiler, false);
+            code.push(Accept);
+            code[pc] = LookAhead(code.len());
+        }
+        ENegativeLookAhead(ref e) => {
+            let pc = code.len();
+            code.push(NegativeLookAhead(0));
+            compile_recursive(*e, code, compiler, false);
+            code.push(Accept);
+            code[pc] = NegativeLookAhead(code.len());
+        }
+        ELookBehind(ref e) => {
+            let pc = code.len();
+            code.push(LookBehind(0));
+            compile_recursive(*e, code, compiler, true);
+            code.push(Accept);
+            code[pc] = LookBehind(code.len());
+        }
+        ENegativeLookBehind(ref e) => {
+            let pc = code.len();
+            code.push(NegativeLookBehind(0));
+            compile_recursive(*e, code, compiler, true);
+            code.push(Accept);
+            code[pc] = NegativeLookBehind(code.len());
+        }
+        EBackreference(group, fold) => code.push(Backreference(group, fold)),
+        // parse replaces these with the groups
+        NamedBackreference(_, _) => unreachable!()
+    }
+}
+
+// Compiles the expression on its own, to be copied into the program with
+// append. The jump targets are relative to its start.
+fn compile_body(e: &Expression, compiler: &mut Compiler, reverse: bool) -> ~[Instruction] {
+    let mut body = ~[];
+    compile_recursive(e, &mut body, compiler, reverse);
+    return body;
+}
+
+// Copies a body from compile_body to the end of the program. Copies can share
+// their registers, since they never run at the same time.
+fn append(code: &mut ~[Instruction], body: &[Instruction]) {
+    let offset = code.len();
+    for instruction in body.iter() {
+        let instr = match *instruction {
+            Fork(pc1, pc2) => Fork(pc1 + offset, pc2 + offset),
+            Jump(pc) => Jump(pc + offset),
+            ConditionalJumpEq(register, value, pc) => ConditionalJumpEq(register, value, pc + offset),
+            ConditionalJumpLE(register, value, pc) => ConditionalJumpLE(register, value, pc + offset),
+            LookAhead(pc) => LookAhead(pc + offset),
+            NegativeLookAhead(pc) => NegativeLookAhead(pc + offset),
+            LookBehind(pc) => LookBehind(pc + offset),
+            NegativeLookBehind(pc) => NegativeLookBehind(pc + offset),
+            ref other => other.clone()
+        };
+        code.push(instr);
+    }
+}
+
+fn compile_star(body: &[Instruction], nullable: bool, typ: QuantifierType, code: &mut ~[Instruction], compiler: &mut Compiler) {
+    // A body that matched the empty string would loop forever, so
+    // such iterations fail, like ECMA-262's RepeatMatcher prescribes
+    // http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.5
+    let check = if nullable { Some(new_register(compiler)) } else { None };
+    let pc = code.len();
+    code.push(Fork(0, 0));
+    save_position(check, code);
+    append(code, body);
+    assert_progress(check, code);
+    let instr = fork(typ, pc + 1, code.len() + 1); 
+    code[pc] = instr;
+    code.push(Jump(pc));
+}
+
+// {low,high}, or {low,} if there is no high. The body is compiled once and
+// copied, so nested repetitions take time linear in the size of the program.
+fn compile_repetition(e: &Expression, low: uint, high: Option<uint>, typ: QuantifierType, code: &mut ~[Instruction], compiler: &mut Compiler, reverse: bool) {
+    let body = compile_body(e, compiler, reverse);
+    let nullable = can_match_empty(e);
+
+    // Every copy may need a fork and a progress check, and {low,} a loop
+    // after the required copies. A count that doesn't fit is too large anyway.
+    let copies = match high {
+        Some(high) => Some(high),
+        None if low < uint::max_value => Some(low + 1),
+        None => None
+    };
+    match copies {
+        Some(copies) if copies <= compiler.unroll_limit / (body.len() + 3) => {
+            compile_unrolled(body, nullable, low, high, typ, code, compiler);
+        }
+        _ => compile_counted(body, nullable, low, high, typ, code, compiler)
+    }
+}
+
+// {low,high} as low copies of the expression followed by high - low optional
+// ones, where skipping one skips all the following ones as well. Unlike the
+// counting loops this only adds plain states for the Pike VM.
+fn compile_unrolled(body: &[Instruction], nullable: bool, low: uint, high: Option<uint>, typ: QuantifierType, code: &mut ~[Instruction], compiler: &mut Compiler) {
+    for _ in range(0, low) {
+        append(code, body);
+    }
+
+    let high = match high {
+        Some(high) => high,
+        None => return compile_star(body, nullable, typ, code, compiler)
+    };
+
+    // The position is only needed until the end of the iteration, so all
+    // of them can share a register
+    let check = if high > low && nullable { Some(new_register(compiler)) } else { None };
+    let mut forks = ~[];
+    for _ in range(low, high) {
+        forks.push(code.len());
+        code.push(Fork(0, 0));
+        save_position(check, code);
+        append(code, body);
+        assert_progress(check, code);
+    }
+    let end = code.len();
+    for &pc in forks.iter() {
+        code[pc] = fork(typ, pc + 1, end);
+    }
+}
+
+// Counts the iterations in a register, for repetitions too large to unroll
+fn compile_counted(body: &[Instruction], nullable: bool, low: uint, high: Option<uint>, typ: QuantifierType, code: &mut ~[Instruction], compiler: &mut Compiler) {
+    let register = new_register(compiler);
+    let check = if nullable && high != Some(low) { Some(new_register(compiler)) } else { None };
+    code.push(ResetRegister(register));
+    let pc = code.len();
+    match high {
+        Some(high) if high == low => {
+            code.push(ConditionalJumpEq(0, 0, 0));
+            append(code, body);
+            code.push(Increment(register));
+            code[pc] = ConditionalJumpEq(register, high, code.len() + 1);
+        }
+        Some(high) => {
             code.push(ConditionalJumpEq(0, 0, 0));
             code.push(ConditionalJumpLE(register, low, pc + 3));
             code.push(Fork(0, 0));
-            compile_recursive(*e, code, registers);
+            save_position(check, code);
+            append(code, body);
+            assert_optional_progress(check, register, low, code);
             code.push(Increment(register));
             code[pc] = ConditionalJumpEq(register, high, code.len() + 1);
             code[pc + 2] = fork(typ, pc + 3, code.len() + 1);
-            code.push(Jump(pc));
         }
-        EAssertStart => code.push(AssertStart),
-        EAssertEnd => code.push(AssertEnd),
-        EAssertWordBoundary => code.push(AssertWordBoundary),
-        EAssertNonWordBoundary => code.push(AssertNonWordBoundary)
+        None => {
+            code.push(ConditionalJumpLE(register, low, pc + 2));
+            code.push(Fork(0, 0));
+            save_position(check, code);
+            append(code, body);
+            assert_optional_progress(check, register, low, code);
+            code.push(Increment(register));
+            code[pc + 1] = fork(typ, pc + 2, code.len() + 1);
+        }
+    }
+    code.push(Jump(pc));
+}
+
+#[inline]
+fn new_register(compiler: &mut Compiler) -> uint {
+    let register = compiler.registers;
+    compiler.registers += 1;
+    return register;
+}
+
+#[inline]
+fn save_position(check: Option<uint>, code: &mut ~[Instruction]) {
+    match check {
+        Some(register) => code.push(SavePosition(register)),
+        None => ()
+    }
+}
+
+#[inline]
+fn assert_progress(check: Option<uint>, code: &mut ~[Instruction]) {
+    match check {
+        Some(register) => code.push(AssertProgress(register)),
+        None => ()
+    }
+}
+
+// Only the iterations after the first low ones have to make progress, the
+// required ones may match the empty string
+#[inline]
+fn assert_optional_progress(check: Option<uint>, counter: uint, low: uint, code: &mut ~[Instruction]) {
+    match check {
+        Some(register) => {
+            let pc = code.len();
+            code.push(ConditionalJumpLE(counter, low, pc + 2));
+            code.push(AssertProgress(register));
+        }
+        None => ()
+    }
+}
+
+// Whether the expression can match without consuming any input
+pub fn can_match_empty(expression: &Expression) -> bool {
+    match *expression {
+        Literal(_) |
+        AnyLiteral |
+        RangeLiteral(_, _) |
+        CharacterClass(_) => false,
+        Concatenate(ref left, ref right) => can_match_empty(*left) && can_match_empty(*right),
+        Alternate(ref left, ref right) => can_match_empty(*left) || can_match_empty(*right),
+        SubExpression(ref e, _, _) |
+        Plus(ref e, _) => can_match_empty(*e),
+        ExactRepetition(ref e, count, _) => count == 0 || can_match_empty(*e),
+        UnboundedRepetition(ref e, low, _) |
+        BoundedRepetition(ref e, low, _, _) => low == 0 || can_match_empty(*e),
+        // Assertions, optional expressions and backreferences to empty or
+        // unmatched groups
+        _ => true
     }
 }
 
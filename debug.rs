@@ -1,11 +1,22 @@
-//use std::str;
+use std::str;
+use std::vec;
+
+use super::case;
 
 use EAssertStart = super::parse::AssertStart;
 use EAssertEnd = super::parse::AssertEnd;
+use EAssertLineStart = super::parse::AssertLineStart;
+use EAssertLineEnd = super::parse::AssertLineEnd;
 use EAssertWordBoundary = super::parse::AssertWordBoundary;
 use EAssertNonWordBoundary = super::parse::AssertNonWordBoundary;
+use ELookAhead = super::parse::LookAhead;
+use ENegativeLookAhead = super::parse::NegativeLookAhead;
+use ELookBehind = super::parse::LookBehind;
+use ENegativeLookBehind = super::parse::NegativeLookBehind;
+use EBackreference = super::parse::Backreference;
 use super::parse::{
     Expression,
+    Empty,
     Literal,
     AnyLiteral,
     RangeLiteral,
@@ -18,8 +29,10 @@ use super::parse::{
     Plus,
     ExactRepetition,
     UnboundedRepetition,
-    BoundedRepetition
+    BoundedRepetition,
+    NamedBackreference
 };
+use super::parse::ParseError;
 use super::parse::{
     QuantifierType,
     Greedy,
@@ -30,21 +43,40 @@ use super::compile::{
     Instruction,
     Char,
     Range,
+    Class,
     Any,
     Fork,
     Jump,
     ConditionalJumpEq,
     ConditionalJumpLE,
     Increment,
+    ResetRegister,
+    SavePosition,
+    AssertProgress,
     SaveStart,
     SaveEnd,
     AssertStart,
     AssertEnd,
+    AssertLineStart,
+    AssertLineEnd,
     AssertWordBoundary,
     AssertNonWordBoundary,
+    LookAhead,
+    NegativeLookAhead,
+    LookBehind,
+    NegativeLookBehind,
+    Backreference,
     Accept
 };
 
+pub fn print_parse_error(pattern: &str, error: &ParseError) {
+    let (start, end) = error.span;
+    let offset = pattern.slice_to(start).char_len();
+    let width = pattern.slice(start, end).char_len();
+    let marker = str::from_chars(vec::from_elem(offset, ' ')) + str::from_chars(vec::from_elem(if width > 0 { width } else { 1 }, '^'));
+    println!("\n{}\n  {}\n  {}\n", error.to_str(), pattern, marker);
+}
+
 pub fn print_expression_tree(expression: &Expression) {
     print_expression_tree_recursive(expression, 0);
 }
@@ -55,6 +87,7 @@ fn print_expression_tree_recursive(expression: &Expression, indent: uint) {
     }
 
     match *expression {
+        Empty => println("Empty"),
         Literal(c) => println!("Literal({})", c),
         AnyLiteral => println("Any"),
         RangeLiteral(start, end) => println!("Range({}, {})", start, end),
@@ -79,10 +112,11 @@ fn print_expression_tree_recursive(expression: &Expression, indent: uint) {
             print_expression_tree_recursive(*left, indent + 1);
             print_expression_tree_recursive(*right, indent + 1);
         }
-        SubExpression(ref e, capture_slot) => {
-            match capture_slot {
-                Some(slot) => println!("Group {}", slot),
-                None => println("Non-capture Group")
+        SubExpression(ref e, capture_slot, ref name) => {
+            match (capture_slot, name) {
+                (Some(slot), &Some(ref name)) => println!("Group {} <{}>", slot, *name),
+                (Some(slot), &None) => println!("Group {}", slot),
+                (None, _) => println("Non-capture Group")
             }
             print_expression_tree_recursive(*e, indent + 1);
         }
@@ -112,8 +146,35 @@ fn print_expression_tree_recursive(expression: &Expression, indent: uint) {
         }
         EAssertStart => println("AssertStart"),
         EAssertEnd => println("AssertEnd"),
-        EAssertWordBoundary => println("AssertWordBoundary"),
-        EAssertNonWordBoundary => println("AssertNonWordBoundary")
+        EAssertLineStart => println("AssertLineStart"),
+        EAssertLineEnd => println("AssertLineEnd"),
+        EAssertWordBoundary(unicode) => println!("AssertWordBoundary{}", if unicode { " Unicode" } else { "" }),
+        EAssertNonWordBoundary(unicode) => println!("AssertNonWordBoundary{}", if unicode { " Unicode" } else { "" }),
+        ELookAhead(ref e) => {
+            println("LookAhead");
+            print_expression_tree_recursive(*e, indent + 1);
+        }
+        ENegativeLookAhead(ref e) => {
+            println("NegativeLookAhead");
+            print_expression_tree_recursive(*e, indent + 1);
+        }
+        ELookBehind(ref e) => {
+            println("LookBehind");
+            print_expression_tree_recursive(*e, indent + 1);
+        }
+        ENegativeLookBehind(ref e) => {
+            println("NegativeLookBehind");
+            print_expression_tree_recursive(*e, indent + 1);
+        }
+        NamedBackreference(ref name, _) => println!("NamedBackreference {}", *name),
+        EBackreference(group, fold) => {
+            println!("Backreference {}{}", group, match fold {
+                Some(case::EcmaFold) => " IgnoreCase",
+                Some(case::SimpleFold) => " IgnoreCase Simple",
+                Some(case::FullFold) => " IgnoreCase Full",
+                None => ""
+            })
+        }
     }
 }
 
@@ -131,18 +192,35 @@ pub fn print_code(instructions: &[Instruction]) {
             Char(c) => println!("CHR {}", c),
             Any => println("ANY"),
             Range(start, end) => println!("RNG {} {}", start, end),
+            Class(ref class) => {
+                print("CLS");
+                for &(start, end) in class.ranges().iter() {
+                    print!(" {}-{}", start, end);
+                }
+                println("");
+            }
             Fork(left, right) => println!("FRK {} {}", left, right),
             Jump(new_pc) => println!("JMP {}", new_pc),
             ConditionalJumpEq(register, value, new_pc) => println!("JEQ {} {} {}", register, value, new_pc),
             ConditionalJumpLE(register, value, new_pc) => println!("JLE {} {} {}", register, value, new_pc),
             Increment(register) => println!("INC {}", register),
+            ResetRegister(register) => println!("RST {}", register),
+            SavePosition(register) => println!("SVP {}", register),
+            AssertProgress(register) => println!("ASP {}", register),
             SaveStart(group) => println!("SVS {}", group),
             SaveEnd(group) => println!("SVE {}", group),
             Accept => println("ACC"),
             AssertStart => println("AS^"),
             AssertEnd => println("AS$"),
-            AssertWordBoundary => println("ASb"),
-            AssertNonWordBoundary => println("ASB")
+            AssertLineStart => println("ASL^"),
+            AssertLineEnd => println("ASL$"),
+            AssertWordBoundary(unicode) => println!("ASb {}", unicode),
+            AssertNonWordBoundary(unicode) => println!("ASB {}", unicode),
+            LookAhead(next_pc) => println!("LKA {}", next_pc),
+            NegativeLookAhead(next_pc) => println!("NLA {}", next_pc),
+            LookBehind(next_pc) => println!("LKB {}", next_pc),
+            NegativeLookBehind(next_pc) => println!("NLB {}", next_pc),
+            Backreference(group, fold) => println!("REF {} {}", group, fold.is_some())
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,231 @@
+#!/usr/bin/perl
+# Generates unicode.rs from the Unicode Character Database bundled with Perl.
+#
+#     perl etc/unicode.pl > unicode.rs
+
+use strict;
+use warnings;
+use feature qw(unicode_strings);
+use Unicode::UCD qw(prop_invlist prop_values prop_value_aliases prop_aliases all_casefolds);
+
+my @categories = qw(Lu Ll Lt Lm Lo Mn Mc Me Nd Nl No Pc Pd Ps Pe Pi Pf Po
+                    Sm Sc Sk So Zs Zl Zp Cc Cf Cs Co Cn);
+my %groups = (
+    LC => [qw(Lu Ll Lt)],
+    L => [qw(Lu Ll Lt Lm Lo)],
+    M => [qw(Mn Mc Me)],
+    N => [qw(Nd Nl No)],
+    P => [qw(Pc Pd Ps Pe Pi Pf Po)],
+    S => [qw(Sm Sc Sk So)],
+    Z => [qw(Zs Zl Zp)],
+    C => [qw(Cc Cf Cs Co Cn)],
+);
+my @binary = qw(Alphabetic Any ASCII ASCII_Hex_Digit Assigned Cased Dash
+                Hex_Digit ID_Continue ID_Start Ideographic Lowercase Math
+                Noncharacter_Code_Point Uppercase White_Space);
+
+# Surrogates can't be represented by char, so they are left out
+sub ranges {
+    my @list = prop_invlist(shift);
+    my @ranges;
+    for (my $i = 0; $i < @list; $i += 2) {
+        my $start = $list[$i];
+        my $end = $i + 1 < @list ? $list[$i + 1] - 1 : 0x10ffff;
+        push @ranges, [$start, $end > 0xd7ff ? 0xd7ff : $end] if $start < 0xd800;
+        push @ranges, [$start < 0xe000 ? 0xe000 : $start, $end] if $end > 0xdfff;
+    }
+    return @ranges;
+}
+
+# Merges the ranges of several properties into one sorted list
+sub union {
+    my @ranges = sort { $a->[0] <=> $b->[0] } map { ranges($_) } @_;
+    my @merged;
+    for my $range (@ranges) {
+        if (@merged && $range->[0] <= $merged[-1][1] + 1) {
+            $merged[-1][1] = $range->[1] if $range->[1] > $merged[-1][1];
+        } else {
+            push @merged, [@$range];
+        }
+    }
+    return @merged;
+}
+
+sub chr_literal {
+    my $c = shift;
+    return $c > 0xffff ? sprintf("'\\U%08x'", $c) : sprintf("'\\u%04x'", $c);
+}
+
+sub table {
+    my ($name, @properties) = @_;
+    my @ranges = union(@properties);
+    print "static $name: &'static [(char, char)] = &[\n";
+    my @items = map { "(" . chr_literal($_->[0]) . ", " . chr_literal($_->[1]) . ")" } @ranges;
+    while (my @line = splice(@items, 0, 4)) {
+        print "    ", join(", ", @line), ",\n";
+    }
+    print "];\n\n";
+}
+
+sub names {
+    my ($property, $value) = @_;
+    my %seen;
+    return join(" | ", map { "\"$_\"" } grep { !$seen{$_}++ } ($value, prop_value_aliases($property, $value)));
+}
+
+my $version = Unicode::UCD::UnicodeVersion();
+
+print <<"HEADER";
+// This file is generated by etc/unicode.pl from the Unicode Character
+// Database, version $version. Do not edit it by hand.
+//
+// Property names follow http://www.unicode.org/reports/tr18/#RL1.2 and are
+// matched exactly, by either their short or long alias.
+
+#[allow(non_uppercase_statics)];
+
+use std::cmp::{Equal, Less, Greater};
+
+fn bsearch_range_table(c: char, table: &'static [(char, char)]) -> bool {
+    table.bsearch(|&(start, end)| {
+        if start <= c && c <= end { Equal }
+        else if end < c { Less }
+        else { Greater }
+    }).is_some()
+}
+
+// Word characters in Unicode mode, \\p{Alphabetic}, \\p{M}, \\p{Nd} and \\p{Pc}
+// http://www.unicode.org/reports/tr18/#Compatibility_Properties
+pub fn is_word_char(c: char) -> bool {
+    bsearch_range_table(c, Word_table)
+}
+
+pub fn word() -> ~[(char, char)] {
+    Word_table.to_owned()
+}
+
+// The simple case folding of c, from the C and S mappings of CaseFolding.txt
+pub fn simple_fold(c: char) -> char {
+    match SimpleFold_table.bsearch(|&(from, _)| {
+        if from == c { Equal }
+        else if from < c { Less }
+        else { Greater }
+    }) {
+        Some(i) => { let (_, to) = SimpleFold_table[i]; to }
+        None => c
+    }
+}
+
+// Every character with a simple case folding other than itself, sorted
+pub fn simple_folds() -> &'static [(char, char)] {
+    SimpleFold_table
+}
+
+// The full case folding of c, if it folds to more than one character, from
+// the F mappings of CaseFolding.txt
+pub fn full_fold(c: char) -> Option<&'static str> {
+    match FullFold_table.bsearch(|&(from, _)| {
+        if from == c { Equal }
+        else if from < c { Less }
+        else { Greater }
+    }) {
+        Some(i) => { let (_, to) = FullFold_table[i]; Some(to) }
+        None => None
+    }
+}
+
+// Every character whose full case folding is s
+pub fn full_fold_sources(s: &str) -> ~[char] {
+    FullFold_table.iter().filter(|& &(_, to)| to == s).map(|& (from, _)| from).collect()
+}
+
+// The upper case of c, as converted by String.prototype.toUpperCase with the
+// full case mappings of UnicodeData.txt and SpecialCasing.txt, or c itself if
+// that isn't a single character
+pub fn to_upper(c: char) -> char {
+    match ToUpper_table.bsearch(|&(from, _)| {
+        if from == c { Equal }
+        else if from < c { Less }
+        else { Greater }
+    }) {
+        Some(i) => { let (_, to) = ToUpper_table[i]; to }
+        None => c
+    }
+}
+
+// Every character with an upper case other than itself, sorted
+pub fn upper_mappings() -> &'static [(char, char)] {
+    ToUpper_table
+}
+
+HEADER
+
+print "pub fn general_category(name: &str) -> Option<~[(char, char)]> {\n";
+print "    let tables: ~[&'static [(char, char)]] = match name {\n";
+for my $gc (@categories) {
+    print "        ", names("gc", $gc), " => ~[${gc}_table],\n";
+}
+for my $group (sort keys %groups) {
+    print "        ", names("gc", $group), " => ~[", join(", ", map { "${_}_table" } @{$groups{$group}}), "],\n";
+}
+print "        _ => return None\n    };\n\n";
+print "    let mut ranges = ~[];\n    for table in tables.iter() {\n        ranges.push_all(*table);\n    }\n\n    return Some(ranges);\n}\n\n";
+
+my @scripts = grep { $_ ne 'Zzzz' } sort(prop_values("Script"));
+print "pub fn script(name: &str) -> Option<~[(char, char)]> {\n";
+print "    let table = match name {\n";
+for my $sc (@scripts) {
+    my $long = (prop_value_aliases("sc", $sc))[1] // $sc;
+    print "        ", names("sc", $sc), " => ${long}_table,\n";
+}
+print "        _ => return None\n    };\n\n    return Some(table.to_owned());\n}\n\n";
+
+print "pub fn binary_property(name: &str) -> Option<~[(char, char)]> {\n";
+print "    let table = match name {\n";
+for my $property (@binary) {
+    my %seen;
+    my $names = join(" | ", map { "\"$_\"" } grep { !$seen{$_}++ } ($property, prop_aliases($property)));
+    print "        $names => ${property}_table,\n";
+}
+print "        _ => return None\n    };\n\n    return Some(table.to_owned());\n}\n\n";
+
+table("${_}_table", "gc=$_") for @categories;
+for my $sc (@scripts) {
+    my $long = (prop_value_aliases("sc", $sc))[1] // $sc;
+    table("${long}_table", "sc=$sc");
+}
+table("${_}_table", $_) for @binary;
+table("Word_table", qw(Alphabetic gc=Mn gc=Mc gc=Me gc=Nd gc=Pc));
+
+my $folds = all_casefolds();
+my @codes = sort { $a <=> $b } keys %$folds;
+my @simple = grep { $folds->{$_}{simple} ne "" } @codes;
+my @full = grep { $folds->{$_}{full} =~ / / } @codes;
+
+print "static SimpleFold_table: &'static [(char, char)] = &[\n";
+my @items = map { "(" . chr_literal($_) . ", " . chr_literal(hex($folds->{$_}{simple})) . ")" } @simple;
+while (my @line = splice(@items, 0, 4)) {
+    print "    ", join(", ", @line), ",\n";
+}
+print "];\n\n";
+
+# uc applies the full mappings without language specific conditions
+my @upper;
+for my $code (0 .. 0x10ffff) {
+    next if $code >= 0xd800 && $code <= 0xdfff;
+    my $to = uc(chr($code));
+    push(@upper, "(" . chr_literal($code) . ", " . chr_literal(ord($to)) . ")") if length($to) == 1 && ord($to) != $code;
+}
+
+print "static ToUpper_table: &'static [(char, char)] = &[\n";
+while (my @line = splice(@upper, 0, 4)) {
+    print "    ", join(", ", @line), ",\n";
+}
+print "];\n\n";
+
+print "static FullFold_table: &'static [(char, &'static str)] = &[\n";
+for my $code (@full) {
+    my $to = join("", map { sprintf(hex($_) > 0xffff ? "\\U%08x" : "\\u%04x", hex($_)) } split(/ /, $folds->{$code}{full}));
+    print "    (", chr_literal($code), ", \"$to\"),\n";
+}
+print "];\n";
@@ -1,5 +1,5 @@
-
 use super::compile;
+use super::unicode;
 
 #[deriving(Clone)]
 pub struct Match {
@@ -8,5 +8,55 @@ pub struct Match {
 }
 
 pub trait Matcher {
-    fn do_match(code: &[compile::Instruction], input: &str) -> Option<~Match>;
-}
\ No newline at end of file
+    // Runs the program starting at start_pc from position start_sp. If anchored, 
+    // only a match starting exactly at start_sp will be found. Returns the 
+    // captures indexed by group, with the full match in 0.
+    fn run(&mut self, start_pc: uint, start_sp: uint, anchored: bool) -> Option<~[Option<Match>]>;
+}
+
+// Reduces the captures of a match to the list of matched groups, full match first
+pub fn compact(captures: Option<~[Option<Match>]>) -> Option<~[Match]> {
+    match captures {
+        Some(captures) => {
+            let mut matches = ~[];
+            for capture in captures.iter() {
+                match capture {
+                    &Some(m) => matches.push(m),
+                    &None => ()
+                }
+            }
+            Some(matches)
+        }
+        None => None
+    }
+}
+
+// Not very international unless unicode is set, but this is the standard
+// http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.6
+pub fn is_word_char(c: char, unicode: bool) -> bool {
+    if unicode {
+        return unicode::is_word_char(c);
+    }
+
+    (c >= 'a' && c <= 'z') ||
+    (c >= 'A' && c <= 'Z') ||
+    (c >= '0' && c <= '9') ||
+    c == '_'
+}
+
+// http://www.ecma-international.org/ecma-262/5.1/#sec-7.3
+pub fn is_line_terminator(c: char) -> bool {
+    match c {
+        '\n' | '\r' | '\u2028' | '\u2029' => true,
+        _ => false
+    }
+}
+
+pub fn uses_backreferences(code: &[compile::Instruction]) -> bool {
+    code.iter().any(|instruction| {
+        match *instruction {
+            compile::Backreference(_, _) => true,
+            _ => false
+        }
+    })
+}
@@ -0,0 +1,237 @@
+use std::uint;
+
+use super::Regex;
+use super::captures;
+use super::captures::{Captures, Match};
+use super::matcher;
+use super::pike;
+
+// Searches for successive non-overlapping matches. The input is decoded and
+// its byte offsets computed once, for all the searches, and the Pike VM
+// works in the same scratch for all of them.
+struct Searcher<'self> {
+    regex: &'self Regex,
+    text: &'self str,
+    chars: ~[char],
+    offsets: ~[uint],
+    scratch: Option<pike::Scratch>, // None for the backtracking matcher
+    sp: uint // Where the next search starts, past the end when done
+}
+
+impl<'self> Searcher<'self> {
+    fn new<'r>(regex: &'r Regex, text: &'r str) -> Searcher<'r> {
+        Searcher {
+            regex: regex,
+            text: text,
+            chars: text.iter().collect(),
+            offsets: captures::byte_offsets(text),
+            scratch: if regex.backtrack { None } else { Some(pike::Scratch::new(regex.code)) },
+            sp: 0
+        }
+    }
+
+    // The groups of the first match at or after the character index start
+    fn search(&mut self, start: uint) -> Option<~[Option<matcher::Match>]> {
+        match self.scratch.take() {
+            Some(scratch) => {
+                let regex = self.regex;
+                let (groups, scratch) = pike::PikeMatcher::search_with(regex.code, self.chars, regex.captures, start, false, scratch);
+                self.scratch = Some(scratch);
+                groups
+            }
+            None => self.regex.search(self.chars, start, false)
+        }
+    }
+
+    fn next(&mut self) -> Option<~[Option<matcher::Match>]> {
+        if self.sp > self.chars.len() {
+            return None;
+        }
+
+        match self.search(self.sp) {
+            Some(groups) => {
+                let m = groups[0].unwrap();
+                // Like lastIndex in String.prototype.replace, an empty match
+                // moves the next search one character ahead so it can't match
+                // at the same place again
+                self.sp = if m.start == m.end { m.end + 1 } else { m.end };
+                Some(groups)
+            }
+            None => {
+                self.sp = self.chars.len() + 1;
+                None
+            }
+        }
+    }
+}
+
+pub struct FindMatches<'self> {
+    priv searcher: Searcher<'self>
+}
+
+impl<'self> FindMatches<'self> {
+    pub fn new<'r>(regex: &'r Regex, text: &'r str) -> FindMatches<'r> {
+        FindMatches { searcher: Searcher::new(regex, text) }
+    }
+}
+
+impl<'self> Iterator<Match<'self>> for FindMatches<'self> {
+    fn next(&mut self) -> Option<Match<'self>> {
+        match self.searcher.next() {
+            Some(groups) => {
+                let m = groups[0].unwrap();
+                let offsets = &self.searcher.offsets;
+                Some(Match::new(self.searcher.text, offsets[m.start], offsets[m.end]))
+            }
+            None => None
+        }
+    }
+}
+
+pub struct FindCaptures<'self> {
+    priv searcher: Searcher<'self>
+}
+
+impl<'self> FindCaptures<'self> {
+    pub fn new<'r>(regex: &'r Regex, text: &'r str) -> FindCaptures<'r> {
+        FindCaptures { searcher: Searcher::new(regex, text) }
+    }
+}
+
+impl<'self> Iterator<Captures<'self>> for FindCaptures<'self> {
+    fn next(&mut self) -> Option<Captures<'self>> {
+        match self.searcher.next() {
+            Some(groups) => {
+                let searcher = &self.searcher;
+                Some(Captures::new(searcher.text, searcher.offsets, groups, &searcher.regex.names))
+            }
+            None => None
+        }
+    }
+}
+
+// The text between successive matches, including before the first and after the last
+pub struct Split<'self> {
+    priv matches: FindMatches<'self>,
+    priv last: uint, // Byte offset of the end of the previous match
+    priv done: bool
+}
+
+impl<'self> Split<'self> {
+    pub fn new<'r>(regex: &'r Regex, text: &'r str) -> Split<'r> {
+        Split { matches: FindMatches::new(regex, text), last: 0, done: false }
+    }
+
+    // The text that hasn't been returned yet, and the end of the iteration
+    fn rest(&mut self) -> Option<&'self str> {
+        if self.done {
+            return None;
+        }
+        self.done = true;
+        Some(self.matches.searcher.text.slice_from(self.last))
+    }
+}
+
+impl<'self> Iterator<&'self str> for Split<'self> {
+    fn next(&mut self) -> Option<&'self str> {
+        if self.done {
+            return None;
+        }
+
+        match self.matches.next() {
+            Some(m) => {
+                let piece = self.matches.searcher.text.slice(self.last, m.start());
+                self.last = m.end();
+                Some(piece)
+            }
+            None => self.rest()
+        }
+    }
+}
+
+// Like Split, but the last of at most limit pieces is all the remaining text
+pub struct SplitN<'self> {
+    priv split: Split<'self>,
+    priv remaining: uint
+}
+
+impl<'self> SplitN<'self> {
+    pub fn new<'r>(regex: &'r Regex, text: &'r str, limit: uint) -> SplitN<'r> {
+        SplitN { split: Split::new(regex, text), remaining: limit }
+    }
+}
+
+impl<'self> Iterator<&'self str> for SplitN<'self> {
+    fn next(&mut self) -> Option<&'self str> {
+        match self.remaining {
+            0 => None,
+            1 => {
+                self.remaining = 0;
+                self.split.rest()
+            }
+            _ => {
+                self.remaining -= 1;
+                self.split.next()
+            }
+        }
+    }
+}
+
+// Splits like String.prototype.split, with the groups of every separator
+// between the pieces and None for those that didn't match. Returns at most
+// limit elements.
+// http://www.ecma-international.org/ecma-262/5.1/#sec-15.5.4.14
+pub fn split_captures<'r>(regex: &'r Regex, text: &'r str, limit: Option<uint>) -> ~[Option<&'r str>] {
+    let mut result = ~[];
+    let limit = match limit {
+        Some(0) => return result,
+        Some(limit) => limit,
+        None => uint::max_value
+    };
+    let mut searcher = Searcher::new(regex, text);
+    let len = searcher.chars.len();
+
+    if len == 0 {
+        match searcher.search(0) {
+            Some(_) => (),
+            None => result.push(Some(text))
+        }
+        return result;
+    }
+
+    // Character indices of the start of the next piece and of the next search
+    let (mut p, mut q) = (0, 0);
+    while q < len {
+        let groups = match searcher.search(q) {
+            Some(groups) => groups,
+            None => break
+        };
+        let m = groups[0].unwrap();
+        // A separator can't start at the end of the text
+        if m.start >= len {
+            break;
+        }
+        // Nor be empty at the start of the piece
+        if m.end == p {
+            q = m.start + 1;
+            continue;
+        }
+
+        let offsets = &searcher.offsets;
+        result.push(Some(text.slice(offsets[p], offsets[m.start])));
+        if result.len() == limit {
+            return result;
+        }
+        for group in groups.iter().skip(1) {
+            result.push(group.map(|g| text.slice(offsets[g.start], offsets[g.end])));
+            if result.len() == limit {
+                return result;
+            }
+        }
+        p = m.end;
+        q = p;
+    }
+
+    result.push(Some(text.slice_from(searcher.offsets[p])));
+    return result;
+}
@@ -1,24 +1,33 @@
-use std::num;
 use std::str;
 use std::char;
 use std::from_str;
 use std::iter;
+use std::hashmap::HashMap;
+
+use super::case;
+use super::case::FoldLevel;
+use super::unicode;
 //use std::vec;
 use extra::sort;
 
+macro_rules! try(
+    ($e:expr) => (match $e { Ok(e) => e, Err(e) => return Err(e) })
+)
+
 pub enum QuantifierType {
 	Greedy,
 	NonGreedy
 }
 
 pub enum Expression {
+    Empty, // matches the empty string, as in () or (a|)
     Literal(char),
     AnyLiteral, // .
     RangeLiteral(char, char), 
     CharacterClass(~[(char, char)]),
     Concatenate(~Expression, ~Expression), // implied
     Alternate(~Expression, ~Expression), // |
-    SubExpression(~Expression, Option<uint>),
+    SubExpression(~Expression, Option<uint>, Option<~str>), // (capture slot, name)
     Question(~Expression, QuantifierType),
     Star(~Expression, QuantifierType),
     Plus(~Expression, QuantifierType),
@@ -27,8 +36,127 @@ pub enum Expression {
     BoundedRepetition(~Expression, uint, uint, QuantifierType), // {x, y}
     AssertStart, // ^
     AssertEnd, // $
-    AssertWordBoundary, // \b
-    AssertNonWordBoundary // \B
+    AssertLineStart, // ^ in multiline mode
+    AssertLineEnd, // $ in multiline mode
+    AssertWordBoundary(bool), // \b (unicode)
+    AssertNonWordBoundary(bool), // \B (unicode)
+    LookAhead(~Expression), // (?=)
+    NegativeLookAhead(~Expression), // (?!)
+    LookBehind(~Expression), // (?<=)
+    NegativeLookBehind(~Expression), // (?<!)
+    Backreference(uint, Option<FoldLevel>), // \1 (group, case folding if ignoring case)
+    NamedBackreference(~str, Option<FoldLevel>) // \k<name> before its group, only until parse resolves it
+}
+
+// Options that change how a pattern is interpreted. They can also be set
+// inline, with (?i) for the rest of the enclosing group or (?i:...) for a
+// single group, and turned off with (?-i).
+#[deriving(Eq, Clone)]
+pub struct Flags {
+    ignore_case: bool, // i
+    multiline: bool, // m, ^ and $ also match at line terminators
+    dot_all: bool, // s, . also matches line terminators
+    unicode_word: bool, // \w, \b and \B use the Unicode definition of word characters
+    fold: FoldLevel // How case is folded when ignoring case
+}
+
+impl Flags {
+    pub fn new() -> Flags {
+        Flags {
+            ignore_case: false,
+            multiline: false,
+            dot_all: false,
+            unicode_word: false,
+            fold: case::EcmaFold
+        }
+    }
+
+    // The case folding to use, or None if case matters
+    pub fn folding(&self) -> Option<FoldLevel> {
+        if self.ignore_case { Some(self.fold) } else { None }
+    }
+}
+
+#[deriving(Eq, Clone)]
+pub enum ParseErrorKind {
+    UnexpectedCloseParen,
+    MissingRangeStart,
+    InvalidRange,
+    UnterminatedCharacterClass,
+    EmptyCharacterClass,
+    IncompleteEscape,
+    InvalidNullEscape,
+    InvalidControlEscape,
+    InvalidHexEscape,
+    InvalidUnicodeEscape,
+    InvalidPropertyEscape,
+    UnknownProperty,
+    UnterminatedGroup,
+    UnexpectedComma,
+    EmptyRepetition,
+    NonNumericRepetition,
+    UnterminatedRepetition,
+    InvalidRepetitionRange,
+    RepetitionTooLarge,
+    MissingOperand,
+    NothingToRepeat,
+    UnexpectedOpenBrace,
+    InvalidBackreference,
+    UnterminatedGroupName,
+    InvalidGroupName,
+    DuplicateGroupName,
+    UndefinedGroupName,
+    InvalidFlag,
+    NotImplemented
+}
+
+impl ParseErrorKind {
+    pub fn message(&self) -> &'static str {
+        match *self {
+            UnexpectedCloseParen => "Unexpected ')' encountered.",
+            MissingRangeStart => "Unexpected '-' in character class. Missing start of range.",
+            InvalidRange => "Character class range start is larger than or equal to range end.",
+            UnterminatedCharacterClass => "Unterminated character class.",
+            EmptyCharacterClass => "Empty character class.",
+            IncompleteEscape => "Incomplete escape sequence.",
+            InvalidNullEscape => "\\0 can not be followed by a decimal digit.",
+            InvalidControlEscape => "\\c must be followed by an ASCII letter.",
+            InvalidHexEscape => "\\x must be followed by two hexadecimal digits.",
+            InvalidUnicodeEscape => "Invalid or unpaired \\u escape.",
+            InvalidPropertyEscape => "\\p and \\P must be followed by {Property} or {Property=Value}.",
+            UnknownProperty => "Unknown Unicode property.",
+            UnterminatedGroup => "Unterminated group.",
+            UnexpectedComma => "Unexpected ',' encountered in repetition.",
+            EmptyRepetition => "Illegal empty repetition.",
+            NonNumericRepetition => "Non-numeric character in repetition.",
+            UnterminatedRepetition => "Unterminated repetition.",
+            InvalidRepetitionRange => "Repetition minimum is larger than maximum.",
+            RepetitionTooLarge => "Repetition count is too large.",
+            MissingOperand => "Missing left operand for operator.",
+            NothingToRepeat => "Nothing to repeat.",
+            UnexpectedOpenBrace => "Unexpected '{' encountered.",
+            InvalidBackreference => "Backreference to non-existent group.",
+            UnterminatedGroupName => "Unterminated group name.",
+            InvalidGroupName => "Illegal character in group name.",
+            DuplicateGroupName => "Duplicate group name.",
+            UndefinedGroupName => "Backreference to undefined group name.",
+            InvalidFlag => "Unknown or misplaced flag.",
+            NotImplemented => "Not implemented."
+        }
+    }
+}
+
+#[deriving(Eq, Clone)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    pos: uint,          // in bytes
+    span: (uint, uint)  // in bytes, end exclusive
+}
+
+impl ToStr for ParseError {
+    fn to_str(&self) -> ~str {
+        format!("Parse error at {}: {}", self.pos, self.kind.message())
+    }
 }
 
 struct Parser<'self> {
@@ -36,17 +164,27 @@ struct Parser<'self> {
     pos: uint,  // in bytes
     next: uint, // in bytes
     current: Option<char>,
-    captures: uint
+    flags: Flags,
+    captures: uint,
+    names: HashMap<~str, uint>,
+    // The highest group referenced and where, to be validated once all groups are known
+    max_backreference: Option<(uint, uint, uint)>, // (group, start, end)
+    // Names referenced before their group, the same way
+    named_backreferences: ~[(~str, uint, uint)] // (name, start, end)
 }
 
 impl<'self> Parser<'self> {
-    fn new<'r> (pattern: &'r str) -> Parser<'r>  {
+    fn new<'r> (pattern: &'r str, flags: Flags) -> Parser<'r>  {
         Parser{
             pattern: pattern,
             pos: 0,
             next: 0,
             current: None,
-            captures: 0
+            flags: flags,
+            captures: 0,
+            names: HashMap::new(),
+            max_backreference: None,
+            named_backreferences: ~[]
         }
     }
 
@@ -87,27 +225,145 @@ impl<'self> Parser<'self> {
         return ret;
     }
 
-    fn fail(&mut self, message: &str) -> ! {
-    	// Too verbose for teting, since task fails always prints the fail message
-    	//let ptr = str::from_chars(vec::from_elem(self.pos, ' ')) + "^";
-    	//let s = format!("Parse error: {}\n  {}\n  {}\n", message, self.pattern, ptr);
-    	let s = "Parse error: " + message;
-        fail!(s);
+    // Fails on the current character
+    fn fail<T>(&self, kind: ParseErrorKind) -> Result<T, ParseError> {
+        let end = if self.current.is_none() { self.pos } else { self.next };
+        self.fail_from(kind, self.pos, end)
+    }
+
+    // Fails on everything from start up to and including the current character
+    fn fail_span<T>(&self, kind: ParseErrorKind, start: uint) -> Result<T, ParseError> {
+        let end = if self.current.is_none() { self.pos } else { self.next };
+        self.fail_from(kind, start, end)
+    }
+
+    fn fail_from<T>(&self, kind: ParseErrorKind, start: uint, end: uint) -> Result<T, ParseError> {
+        Err(ParseError { kind: kind, pos: self.pos, span: (start, end) })
     }
 }
 
-pub fn parse(pattern: &str) -> Expression {
-	let mut parser = Parser::new(pattern);
+pub fn parse(pattern: &str, flags: Flags) -> Result<Expression, ParseError> {
+	let mut parser = Parser::new(pattern, flags);
 
-    let e = parse_recursive(&mut parser);
+    let mut e = try!(parse_recursive(&mut parser));
 
     if parser.pos < pattern.len() {
     	// Inferred since parse_recursive only terminates on end of string or 
     	// encountering a ')'. And since we haven't reached end of string...
-    	parser.fail("Unexpected ')' encountered.");
+    	return parser.fail(UnexpectedCloseParen);
+    }
+
+    match parser.max_backreference {
+        Some((group, start, end)) if group > parser.captures => {
+            return parser.fail_from(InvalidBackreference, start, end);
+        }
+        _ => ()
+    }
+
+    for &(ref name, start, end) in parser.named_backreferences.iter() {
+        if !parser.names.contains_key(name) {
+            return parser.fail_from(UndefinedGroupName, start, end);
+        }
+    }
+    if !parser.named_backreferences.is_empty() {
+        resolve_named_backreferences(&mut e, &parser.names);
     }
 
-    return e;
+    return Ok(e);
+}
+
+// Replaces the references to names defined after them with the groups
+fn resolve_named_backreferences(expression: &mut Expression, names: &HashMap<~str, uint>) {
+    let resolved = match *expression {
+        NamedBackreference(ref name, fold) => Backreference(*names.get(name), fold),
+        Concatenate(ref mut left, ref mut right) |
+        Alternate(ref mut left, ref mut right) => {
+            resolve_named_backreferences(&mut **left, names);
+            resolve_named_backreferences(&mut **right, names);
+            return;
+        }
+        SubExpression(ref mut e, _, _) |
+        Question(ref mut e, _) |
+        Star(ref mut e, _) |
+        Plus(ref mut e, _) |
+        ExactRepetition(ref mut e, _, _) |
+        UnboundedRepetition(ref mut e, _, _) |
+        BoundedRepetition(ref mut e, _, _, _) |
+        LookAhead(ref mut e) |
+        NegativeLookAhead(ref mut e) |
+        LookBehind(ref mut e) |
+        NegativeLookBehind(ref mut e) => {
+            resolve_named_backreferences(&mut **e, names);
+            return;
+        }
+        _ => return
+    };
+    *expression = resolved;
+}
+
+// Collects the names of all named groups with their capture slots
+pub fn capture_names(expression: &Expression) -> HashMap<~str, uint> {
+    let mut names = HashMap::new();
+    capture_names_recursive(expression, &mut names);
+    return names;
+}
+
+fn capture_names_recursive(expression: &Expression, names: &mut HashMap<~str, uint>) {
+    match *expression {
+        SubExpression(ref e, capture_slot, ref name) => {
+            match (capture_slot, name) {
+                (Some(slot), &Some(ref name)) => { names.insert(name.clone(), slot); }
+                _ => ()
+            }
+            capture_names_recursive(*e, names);
+        }
+        Concatenate(ref left, ref right) |
+        Alternate(ref left, ref right) => {
+            capture_names_recursive(*left, names);
+            capture_names_recursive(*right, names);
+        }
+        Question(ref e, _) |
+        Star(ref e, _) |
+        Plus(ref e, _) |
+        ExactRepetition(ref e, _, _) |
+        UnboundedRepetition(ref e, _, _) |
+        BoundedRepetition(ref e, _, _, _) |
+        LookAhead(ref e) |
+        NegativeLookAhead(ref e) |
+        LookBehind(ref e) |
+        NegativeLookBehind(ref e) => capture_names_recursive(*e, names),
+        _ => ()
+    }
+}
+
+// The number of capture slots, including 0 for the full match. Groups the
+// compiler leaves out, like in (a){0}, still count.
+pub fn count_captures(expression: &Expression) -> uint {
+    match *expression {
+        SubExpression(ref e, capture_slot, _) => {
+            let inner = count_captures(*e);
+            match capture_slot {
+                Some(slot) if slot + 1 > inner => slot + 1,
+                _ => inner
+            }
+        }
+        Concatenate(ref left, ref right) |
+        Alternate(ref left, ref right) => {
+            let (l, r) = (count_captures(*left), count_captures(*right));
+            if l > r { l } else { r }
+        }
+        Question(ref e, _) |
+        Star(ref e, _) |
+        Plus(ref e, _) |
+        ExactRepetition(ref e, _, _) |
+        UnboundedRepetition(ref e, _, _) |
+        BoundedRepetition(ref e, _, _, _) |
+        LookAhead(ref e) |
+        NegativeLookAhead(ref e) |
+        LookBehind(ref e) |
+        NegativeLookBehind(ref e) => count_captures(*e),
+        _ => 1
+    }
 }
 
 #[inline]
@@ -126,35 +382,48 @@ fn do_alternate(stack: &mut ~[Expression]) {
     }
 }
 
+// The characters before and after c, skipping the surrogates which can't be chars
+#[inline]
+fn prev_char(c: char) -> char {
+    if c == '\ue000' { '\ud7ff' } else { char::from_u32(c as u32 - 1).unwrap() }
+}
+
+#[inline]
+fn next_char(c: char) -> Option<char> {
+    if c == '\ud7ff' { Some('\ue000') } else { char::from_u32(c as u32 + 1) }
+}
+
 #[inline]
-fn negate_char_ranges(ranges: &[(char, char)]) -> Expression {
+fn negate_char_ranges(ranges: &[(char, char)]) -> ~[(char, char)] {
     let mut inverted_ranges = ~[];
 
     let sorted_ranges = sort::merge_sort(ranges, |v1, v2| v1.first() <= v2.first());
 
-    let mut start = '\0';
-    let mut end = '\0';
+    // The first character not yet known to be covered by the ranges
+    let mut start = Some('\0');
     for &(rstart, rend) in sorted_ranges.iter() {
-        if rstart > end {
-            match char::from_u32(rstart as u32 - 1) {
-                Some(c) => inverted_ranges.push((start, c)),
-                None => inverted_ranges.push((start, rstart))
-            }
+        match start {
+            Some(c) if rstart > c => inverted_ranges.push((c, prev_char(rstart))),
+            Some(_) => (),
+            None => break
+        }
+        match start {
+            Some(c) if rend < c => (),
+            _ => start = next_char(rend)
         }
-        start = match char::from_u32(num::max(end as u32, rend as u32 + 1)) {
-            Some(c) => c,
-            None => rend
-        };
-        end = start;
     }
 
-    inverted_ranges.push((start, char::MAX));
+    match start {
+        Some(c) => inverted_ranges.push((c, char::MAX)),
+        None => ()
+    }
 
-    return CharacterClass(inverted_ranges);
+    return inverted_ranges;
 }
 
 #[inline]
-fn parse_charclass(parser: &mut Parser) -> Expression {
+fn parse_charclass(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let start = parser.pos;
     let mut ranges: ~[(char, char)] = ~[];
     let mut negated = false;
 
@@ -162,7 +431,7 @@ fn parse_charclass(parser: &mut Parser) -> Expression {
         match parser.next() {
             Some('^') if ranges.is_empty() && !negated => negated = true,
             Some('\\') => {
-            	match parse_charclass_escape(parser) {
+            	match try!(parse_charclass_escape(parser)) {
             		CharacterClass(r) => ranges.push_all(r),
             		Literal(c) => ranges.push((c, c)),
             		_ => unreachable!()
@@ -171,44 +440,58 @@ fn parse_charclass(parser: &mut Parser) -> Expression {
             Some('-') => {
                 match ranges.pop_opt() {
                     Some((last_start, last_end)) => {
-                        match parser.next() {
+                        let range_start = parser.pos;
+                        let end = match parser.next() {
                             Some(']') => {
                             	ranges.push(('-', '-'));
                             	break;
                             }
-                            Some(end) => {
-		                        if (last_start != last_end) {
-		                        	parser.fail("Unexpected '-' in character class. Missing start of range.");
-		                        }
-                                if last_start >= end {
-                                    parser.fail("Character class range start is larger than or equal to range end.");
+                            Some('\\') => {
+                                match try!(parse_charclass_escape(parser)) {
+                                    Literal(c) => c,
+                                    _ => return parser.fail_span(MissingRangeStart, range_start)
                                 }
-                                ranges.push((last_start, end));
                             }
-                            None => parser.fail("Unterminated character class.")
+                            Some(c) => c,
+                            None => return parser.fail_span(UnterminatedCharacterClass, start)
+                        };
+
+                        if (last_start != last_end) {
+                            return parser.fail_span(MissingRangeStart, range_start);
                         }
+                        if last_start >= end {
+                            return parser.fail_span(InvalidRange, range_start);
+                        }
+                        ranges.push((last_start, end));
                     }
                     None => ranges.push(('-', '-'))
                 }
             }
             Some(']') => break,
             Some(c) => ranges.push((c, c)),
-            None => parser.fail("Unterminated character class.")
+            None => return parser.fail_span(UnterminatedCharacterClass, start)
         }
     }
 
     if ranges.is_empty() {
-        parser.fail("Empty character class");
+        return parser.fail_span(EmptyCharacterClass, start);
+    }
+    match parser.flags.folding() {
+        Some(level) => ranges = case::fold_ranges(ranges, level),
+        None => ()
     }
+
     if negated {
-        return negate_char_ranges(ranges);
+        return Ok(CharacterClass(negate_char_ranges(ranges)));
     } else {
-        return CharacterClass(ranges);
+        return Ok(CharacterClass(ranges));
     }
 }
 
+// The ranges are folded at the given level, if any. Inside a class they
+// aren't, since the class folds all of its ranges at once.
 #[inline]
-fn parse_common_escape(c: char) -> Option<Expression> {
+fn parse_common_escape(c: char, flags: &Flags, fold: Option<FoldLevel>) -> Option<Expression> {
     let ranges = match c {
         'd' |
         'D' => ~[('0', '9')],
@@ -224,50 +507,404 @@ fn parse_common_escape(c: char) -> Option<Expression> {
                  ('\u00a0', '\u00a0'), // No-break Space
                  ('\ufeff', '\ufeff')], // Byte Order Mark
 
+        'w' |
+        'W' if flags.unicode_word => unicode::word(),
+
         'w' |
         'W' => ~[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
 
         _ => return None
     };
 
-    if c.is_uppercase() {
-        Some(negate_char_ranges(ranges))
-    } else {
-        Some(CharacterClass(ranges))
+    let ranges = if c.is_uppercase() { negate_char_ranges(ranges) } else { ranges };
+    match fold {
+        Some(level) => Some(CharacterClass(case::fold_ranges(ranges, level))),
+        None => Some(CharacterClass(ranges))
+    }
+}
+
+// Parses the CharacterEscape the parser is currently at, which is valid both
+// inside and outside of character classes. Returns None if c does not start
+// one. Any other character is taken literally, as an identity escape.
+// http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.10
+fn parse_character_escape(parser: &mut Parser, c: char, start: uint) -> Result<Option<char>, ParseError> {
+    match c {
+        't' => Ok(Some('\t')),
+        'n' => Ok(Some('\n')),
+        'v' => Ok(Some('\x0b')),
+        'f' => Ok(Some('\x0c')),
+        'r' => Ok(Some('\r')),
+        '0' => {
+            match parser.peek(1) {
+                Some(d) if d >= '0' && d <= '9' => {
+                    parser.next();
+                    parser.fail_span(InvalidNullEscape, start)
+                }
+                _ => Ok(Some('\0'))
+            }
+        }
+        'c' => {
+            match parser.peek(1) {
+                Some(l) if (l >= 'a' && l <= 'z') || (l >= 'A' && l <= 'Z') => {
+                    parser.next();
+                    Ok(char::from_u32(l as u32 % 32))
+                }
+                _ => parser.fail_span(InvalidControlEscape, start)
+            }
+        }
+        'x' => {
+            match parse_hex_digits(parser, 2) {
+                Some(value) => Ok(char::from_u32(value)),
+                None => parser.fail_span(InvalidHexEscape, start)
+            }
+        }
+        'u' => {
+            match parse_unicode_escape(parser) {
+                Some(c) => Ok(Some(c)),
+                None => parser.fail_span(InvalidUnicodeEscape, start)
+            }
+        }
+        _ => Ok(None)
+    }
+}
+
+// Consumes exactly count hexadecimal digits
+#[inline]
+fn parse_hex_digits(parser: &mut Parser, count: uint) -> Option<u32> {
+    let mut value = 0u32;
+
+    for _ in iter::range(0, count) {
+        match parser.peek(1) {
+            Some(c) if c.is_digit_radix(16) => {
+                parser.next();
+                value = value * 16 + c.to_digit(16).unwrap() as u32;
+            }
+            _ => return None
+        }
+    }
+
+    return Some(value);
+}
+
+// Parses \uXXXX, a surrogate pair written as \uXXXX\uXXXX, or \u{X...}. 
+// Unpaired surrogates can't occur in a str, and are rejected.
+fn parse_unicode_escape(parser: &mut Parser) -> Option<char> {
+    if parser.peek(1) == Some('{') {
+        parser.next();
+        let mut value = 0u32;
+        let mut digits = 0;
+
+        loop {
+            match parser.next() {
+                Some('}') if digits > 0 => return char::from_u32(value),
+                Some(c) if c.is_digit_radix(16) && value <= 0x10ffff => {
+                    value = value * 16 + c.to_digit(16).unwrap() as u32;
+                    digits += 1;
+                }
+                _ => return None
+            }
+        }
     }
+
+    let high = match parse_hex_digits(parser, 4) {
+        Some(value) => value,
+        None => return None
+    };
+
+    if high >= 0xd800 && high <= 0xdbff && parser.peek(1) == Some('\\') && parser.peek(2) == Some('u') {
+        parser.consume_chars(2);
+        match parse_hex_digits(parser, 4) {
+            Some(low) if low >= 0xdc00 && low <= 0xdfff => {
+                return char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00));
+            }
+            _ => return None
+        }
+    }
+
+    return char::from_u32(high);
+}
+
+// Parses \p{Property}, \p{Property=Value} and the negated \P forms. Lone
+// names are general categories or binary properties, and the only properties
+// that take a value are General_Category and Script.
+// http://www.unicode.org/reports/tr18/#RL1.2
+fn parse_property_escape(parser: &mut Parser, start: uint, fold: Option<FoldLevel>) -> Result<Expression, ParseError> {
+    let negated = parser.current == Some('P');
+
+    if parser.next() != Some('{') {
+        return parser.fail_span(InvalidPropertyEscape, start);
+    }
+
+    let name_start = parser.next;
+    loop {
+        match parser.next() {
+            Some('}') => break,
+            Some(c) if c.is_alphanumeric() || c == '_' || c == '=' => (),
+            _ => return parser.fail_span(InvalidPropertyEscape, start)
+        }
+    }
+
+    let name = parser.pattern.slice(name_start, parser.pos);
+    let ranges = match name.find('=') {
+        Some(i) => {
+            match name.slice_to(i) {
+                "General_Category" | "gc" => unicode::general_category(name.slice_from(i + 1)),
+                "Script" | "sc" => unicode::script(name.slice_from(i + 1)),
+                _ => None
+            }
+        }
+        None => {
+            match unicode::general_category(name) {
+                Some(ranges) => Some(ranges),
+                None => unicode::binary_property(name)
+            }
+        }
+    };
+
+    let mut ranges = match ranges {
+        Some(ranges) => ranges,
+        None => return parser.fail_from(UnknownProperty, name_start, parser.pos)
+    };
+
+    if negated {
+        ranges = negate_char_ranges(ranges);
+    }
+    match fold {
+        Some(level) => ranges = case::fold_ranges(ranges, level),
+        None => ()
+    }
+
+    return Ok(CharacterClass(ranges));
 }
 
 #[inline]
-fn parse_charclass_escape(parser: &mut Parser) -> Expression {
+fn parse_charclass_escape(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let start = parser.pos;
+
 	match parser.next() {
+        Some('b') => Ok(Literal('\x08')), // Backspace
+        Some('p') | Some('P') => parse_property_escape(parser, start, None),
         Some(c) => {
-            match parse_common_escape(c) {
-                Some(e) => e,
-                None => Literal(c)
+            match try!(parse_character_escape(parser, c, start)) {
+                Some(c) => return Ok(Literal(c)),
+                None => ()
+            }
+            match parse_common_escape(c, &parser.flags, None) {
+                Some(e) => Ok(e),
+                None => Ok(Literal(c))
             }
         }
-		None => parser.fail("Incomplete escape sequence.")
+		None => parser.fail_span(IncompleteEscape, parser.pos - 1)
 	}
 }
 
 #[inline]
-fn parse_escape(parser: &mut Parser) -> Expression {
+fn parse_escape(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let start = parser.pos;
+    let fold = parser.flags.folding();
+
     match parser.next() {
-        Some('b') => AssertWordBoundary,
-        Some('B') => AssertNonWordBoundary,
+        Some('b') => Ok(AssertWordBoundary(parser.flags.unicode_word)),
+        Some('B') => Ok(AssertNonWordBoundary(parser.flags.unicode_word)),
+        Some('p') | Some('P') => parse_property_escape(parser, start, fold),
+        Some(c) if c >= '1' && c <= '9' => parse_backreference(parser),
+        Some('k') if parser.peek(1) == Some('<') => {
+            parser.next();
+            let start = parser.next;
+            let name = try!(parse_group_name(parser, '>'));
+            parse_named_backreference(parser, name, start)
+        }
         Some(c) => {
-            match parse_common_escape(c) {
-                Some(e) => e,
-                None => Literal(c)
+            match try!(parse_character_escape(parser, c, start)) {
+                Some(c) => return Ok(parse_literal(parser, c)),
+                None => ()
+            }
+            match parse_common_escape(c, &parser.flags, fold) {
+                Some(e) => Ok(e),
+                None => Ok(parse_literal(parser, c))
+            }
+        }
+        None => parser.fail_span(IncompleteEscape, parser.pos - 1)
+    }
+}
+
+#[inline]
+fn parse_literal(parser: &mut Parser, c: char) -> Expression {
+    match parser.flags.folding() {
+        Some(case::FullFold) => {
+            match unicode::full_fold(c) {
+                Some(sequence) => {
+                    let chars: ~[char] = sequence.iter().collect();
+                    Alternate(~fold_literal(c, case::FullFold), ~fold_sequence(chars, case::FullFold))
+                }
+                None => fold_literal(c, case::FullFold)
+            }
+        }
+        Some(level) => fold_literal(c, level),
+        None => Literal(c)
+    }
+}
+
+#[inline]
+fn fold_literal(c: char, level: FoldLevel) -> Expression {
+    let ranges = case::fold_ranges([(c, c)], level);
+    if ranges.len() > 1 {
+        CharacterClass(ranges)
+    } else {
+        Literal(c)
+    }
+}
+
+#[inline]
+fn fold_sequence(chars: &[char], level: FoldLevel) -> Expression {
+    let mut stack = chars.map(|&c| fold_literal(c, level));
+    do_concat(&mut stack);
+    stack.pop()
+}
+
+#[inline]
+fn is_special(c: char) -> bool {
+    match c {
+        '\\' | '(' | ')' | '[' | '{' | '|' | '.' | '*' | '+' | '?' | '^' | '$' => true,
+        _ => false
+    }
+}
+
+#[inline]
+fn is_quantifier(c: char) -> bool {
+    match c {
+        '*' | '+' | '?' | '{' => true,
+        _ => false
+    }
+}
+
+// With full case folding, a sequence of literals like ss also matches the
+// characters that fold to it, like \u00df. Only unescaped literals are combined,
+// from the left, and not if the last one is quantified.
+fn parse_folded_sequence(parser: &mut Parser, c: char) -> Option<Expression> {
+    if parser.flags.folding() != Some(case::FullFold) || unicode::full_fold(c).is_some() {
+        return None;
+    }
+
+    // Case folded sequences are two or three characters long
+    for &len in [3u, 2].iter() {
+        let mut chars = ~[c];
+        for i in iter::range(1, len) {
+            match parser.peek(i) {
+                Some(next) if !is_special(next) && unicode::full_fold(next).is_none() => chars.push(next),
+                _ => break
+            }
+        }
+
+        if chars.len() < len {
+            continue;
+        }
+        match parser.peek(len) {
+            Some(next) if is_quantifier(next) => continue,
+            _ => ()
+        }
+
+        let folded: ~str = chars.iter().map(|&c| unicode::simple_fold(c)).collect();
+        let sources = unicode::full_fold_sources(folded);
+        if sources.is_empty() {
+            continue;
+        }
+
+        parser.consume_chars(len - 1);
+        let ranges = sources.map(|&c| (c, c));
+        let sequence = fold_sequence(chars, case::FullFold);
+        return Some(Alternate(~sequence, ~CharacterClass(case::fold_ranges(ranges, case::FullFold))));
+    }
+
+    return None;
+}
+
+#[inline]
+fn is_flag(c: char) -> bool {
+    match c {
+        'i' | 'm' | 's' => true,
+        _ => false
+    }
+}
+
+#[inline]
+fn is_flag_group(parser: &mut Parser) -> bool {
+    match (parser.peek(1), parser.peek(2)) {
+        (Some('?'), Some(c)) => c == '-' || is_flag(c),
+        _ => false
+    }
+}
+
+// Parses (?flags-flags) which modifies the flags of the enclosing group and
+// returns nothing, or (?flags-flags:...) which only applies to its own group.
+fn parse_flag_group(parser: &mut Parser) -> Result<Option<Expression>, ParseError> {
+    let start = parser.pos;
+    let mut flags = parser.flags.clone();
+    let mut enable = true;
+
+    parser.next(); // ?
+
+    loop {
+        match parser.next() {
+            Some('i') => flags.ignore_case = enable,
+            Some('m') => flags.multiline = enable,
+            Some('s') => flags.dot_all = enable,
+            Some('-') if enable => enable = false,
+            Some(')') => {
+                parser.flags = flags;
+                return Ok(None);
+            }
+            Some(':') => {
+                let outer = parser.flags.clone();
+                parser.flags = flags;
+                let e = try!(parse_group_body(parser, start));
+                parser.flags = outer;
+                return Ok(Some(SubExpression(~e, None, None)));
+            }
+            Some(_) => return parser.fail(InvalidFlag),
+            None => return parser.fail_span(UnterminatedGroup, start)
+        }
+    }
+}
+
+// Parses the decimal escape the parser is currently at. References to groups
+// not yet defined are allowed, but checked once the whole pattern is parsed.
+#[inline]
+fn parse_backreference(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let start = parser.pos - 1;
+    let mut buffer = str::from_char(parser.current.unwrap());
+
+    loop {
+        match parser.peek(1) {
+            Some(c) if c >= '0' && c <= '9' => {
+                buffer.push_char(c);
+                parser.next();
             }
+            _ => break
         }
-        None => parser.fail("Incomplete escape sequence.")
     }
+
+    let group: uint = match from_str::from_str(buffer) {
+        Some(n) => n,
+        None => return parser.fail_span(InvalidBackreference, start)
+    };
+
+    let is_max = match parser.max_backreference {
+        Some((max, _, _)) => group > max,
+        None => true
+    };
+    if is_max {
+        parser.max_backreference = Some((group, start, parser.next));
+    }
+
+    return Ok(Backreference(group, parser.flags.folding()));
 }
 
 #[inline]
-fn parse_group(parser: &mut Parser) -> Expression {
+fn parse_group(parser: &mut Parser) -> Result<Expression, ParseError> {
+    let start = parser.pos;
     let mut capture = false;
+    let mut name = None;
 
 	match parser.peek(1) {
 		Some ('?') => {
@@ -276,21 +913,75 @@ fn parse_group(parser: &mut Parser) -> Expression {
 				Some(':') => parser.consume_chars(2),
 
                 //Positive lookahead
-				Some('=') => parser.fail("NOT IMPLEMENTED"),
+				Some('=') => {
+                    parser.consume_chars(2);
+                    let e = try!(parse_group_body(parser, start));
+                    return Ok(LookAhead(~e));
+                }
 
                 //Negative lookahead
-				Some('!') =>parser.fail("NOT IMPLEMENTED"),
+				Some('!') => {
+                    parser.consume_chars(2);
+                    let e = try!(parse_group_body(parser, start));
+                    return Ok(NegativeLookAhead(~e));
+                }
+
+                // Lookbehind
+                Some('<') if parser.peek(3) == Some('=') || parser.peek(3) == Some('!') => {
+                    parser.consume_chars(3);
+                    let negated = parser.current == Some('!');
+                    let e = try!(parse_group_body(parser, start));
+                    if negated {
+                        return Ok(NegativeLookBehind(~e));
+                    } else {
+                        return Ok(LookBehind(~e));
+                    }
+                }
+
+                // Named capturing group
+                Some('<') => {
+                    parser.consume_chars(2);
+                    name = Some(try!(parse_group_name(parser, '>')));
+                    capture = true;
+                }
+
+                // Python style named capturing group and backreference
+                Some('P') => {
+                    match parser.peek(3) {
+                        Some('<') => {
+                            parser.consume_chars(3);
+                            name = Some(try!(parse_group_name(parser, '>')));
+                            capture = true;
+                        }
+                        Some('=') => {
+                            parser.consume_chars(3);
+                            let name_start = parser.next;
+                            let name = try!(parse_group_name(parser, ')'));
+                            return parse_named_backreference(parser, name, name_start);
+                        }
+                        _ => {
+                            parser.consume_chars(3);
+                            return parser.fail_span(InvalidGroupName, start);
+                        }
+                    }
+                }
 
                 // Normal capturing group
 				Some(_) => capture = true,
 
-				None => parser.fail("Unterminated sub expression.")
+				None => {
+                    parser.consume_chars(2);
+                    return parser.fail_span(UnterminatedGroup, start);
+                }
 			}
 		}
         // Normal capturing group
 		Some(_) => capture = true,
 
-		None => parser.fail("Unterminated sub expression.")
+		None => {
+            parser.next();
+            return parser.fail_span(UnterminatedGroup, start);
+        }
 	}
 
 	
@@ -301,16 +992,110 @@ fn parse_group(parser: &mut Parser) -> Expression {
         None
     };
 
-	let e = parse_recursive(parser);
+    match name {
+        Some(ref name) => {
+            if !parser.names.insert(name.clone(), parser.captures) {
+                return parser.fail_span(DuplicateGroupName, start);
+            }
+        }
+        None => ()
+    }
+
+	let e = try!(parse_group_body(parser, start));
+
+    return Ok(SubExpression(~e, capture_slot, name));
+}
+
+// Parses a group name up to the terminator, which is consumed. Names follow 
+// the rules for identifiers: a letter, '_' or '$' followed by any number of 
+// letters, digits, '_' or '$'.
+#[inline]
+fn parse_group_name(parser: &mut Parser, terminator: char) -> Result<~str, ParseError> {
+    let start = parser.next;
+    let mut name = ~"";
+
+    loop {
+        match parser.next() {
+            Some(c) if c == terminator => break,
+            Some(c) if c.is_alphabetic() || c == '_' || c == '$' => name.push_char(c),
+            Some(c) if c.is_digit() && !name.is_empty() => name.push_char(c),
+            Some(_) => return parser.fail(InvalidGroupName),
+            None => return parser.fail_span(UnterminatedGroupName, start)
+        }
+    }
+
+    if name.is_empty() {
+        return parser.fail(InvalidGroupName);
+    }
+
+    return Ok(name);
+}
+
+// Named groups must be defined before they are referenced
+#[inline]
+fn parse_named_backreference(parser: &mut Parser, name: ~str, start: uint) -> Result<Expression, ParseError> {
+    match parser.names.find(&name) {
+        Some(&group) => Ok(Backreference(group, parser.flags.folding())),
+        // ECMA-262 allows referencing a group defined later, which like any
+        // group that hasn't matched yet matches the empty string
+        None => {
+            parser.named_backreferences.push((name.clone(), start, parser.pos));
+            Ok(NamedBackreference(name, parser.flags.folding()))
+        }
+    }
+}
+
+// Flags set inside a group only apply until the end of the group
+#[inline]
+fn parse_group_body(parser: &mut Parser, start: uint) -> Result<Expression, ParseError> {
+    let flags = parser.flags.clone();
+	let e = try!(parse_recursive(parser));
+    parser.flags = flags;
 
     match parser.current {
-        Some(')') => return SubExpression(~e, capture_slot),
-        _ => parser.fail("Unterminated group")
-    }	
+        Some(')') => Ok(e),
+        _ => parser.fail_span(UnterminatedGroup, start)
+    }
 }
 
+// Assertions can not be quantified, and neither can quantifiers since a**
+// and a+* aren't valid ECMAScript. Lookaheads are the exception, as
+// QuantifiableAssertion in Annex B of ECMA-262 allows them like browsers do.
+// Quantified expressions that can match the empty string are fine, see
+// compile.rs for how their loops terminate.
 #[inline]
-fn parse_repetition(parser: &mut Parser, expr: Expression) -> Expression {
+fn is_quantifiable(expr: &Expression) -> bool {
+    match *expr {
+        Question(_, _) |
+        Star(_, _) |
+        Plus(_, _) |
+        ExactRepetition(_, _, _) |
+        UnboundedRepetition(_, _, _) |
+        BoundedRepetition(_, _, _, _) |
+        AssertStart |
+        AssertEnd |
+        AssertLineStart |
+        AssertLineEnd |
+        AssertWordBoundary(_) |
+        AssertNonWordBoundary(_) |
+        LookBehind(_) |
+        NegativeLookBehind(_) => false,
+        _ => true
+    }
+}
+
+#[inline]
+// The number of a repetition bound, which has to fit in a uint
+#[inline]
+fn parse_repetition_count(parser: &Parser, buffer: &str, start: uint) -> Result<uint, ParseError> {
+    match from_str::from_str(buffer) {
+        Some(n) => Ok(n),
+        None => parser.fail_span(RepetitionTooLarge, start)
+    }
+}
+
+fn parse_repetition(parser: &mut Parser, expr: Expression) -> Result<Expression, ParseError> {
+    let start = parser.pos;
 	let mut low = None;
 	let mut buffer = ~"";
 
@@ -318,67 +1103,69 @@ fn parse_repetition(parser: &mut Parser, expr: Expression) -> Expression {
 		match parser.next() {
 			Some(c) if c >= '0' && c <= '9' => buffer.push_char(c),
 			Some(',') => {
-				if buffer.len() == 0 && low.is_none() {
-					parser.fail("Unexpected ',' encountered in repetition.");
+				if buffer.len() == 0 || low.is_some() {
+					return parser.fail(UnexpectedComma);
 				}
-				low = from_str::from_str(buffer);
+				low = Some(try!(parse_repetition_count(parser, buffer, start)));
 				buffer.clear();
 			}
 			Some('}') => {
 				if buffer.len() == 0 {
 					match low {
-						Some(n) => return UnboundedRepetition(~expr, n, Greedy),
-						None => parser.fail("Illegal empty repetition.")
+						Some(n) => return Ok(UnboundedRepetition(~expr, n, Greedy)),
+						None => return parser.fail_span(EmptyRepetition, start)
 					}
 				} else {
-					let n = from_str::from_str(buffer).unwrap();
+					let n = try!(parse_repetition_count(parser, buffer, start));
 					match low {
-						Some(l) => return BoundedRepetition(~expr, l, n, Greedy),
-						None => return ExactRepetition(~expr, n, Greedy)
+						Some(l) if l > n => return parser.fail_span(InvalidRepetitionRange, start),
+						Some(l) => return Ok(BoundedRepetition(~expr, l, n, Greedy)),
+						None => return Ok(ExactRepetition(~expr, n, Greedy))
 					}
 				}
 			}
-			Some(_) => parser.fail("Non-numeric character in repetition."),
-			None => parser.fail("Unterminated repetition.")
+			Some(_) => return parser.fail(NonNumericRepetition),
+			None => return parser.fail_span(UnterminatedRepetition, start)
 		}
 	}
 }
 
-fn parse_recursive(parser: &mut Parser) -> Expression {
+fn parse_recursive(parser: &mut Parser) -> Result<Expression, ParseError> {
     let mut stack = ~[];
 
     while (true) {
         match parser.next() {
-            Some('.') => stack.push(AnyLiteral),
+            Some('.') if parser.flags.dot_all => stack.push(AnyLiteral),
+            // Everything but line terminators
+            // http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.8
+            Some('.') => stack.push(CharacterClass(negate_char_ranges([('\n', '\n'), ('\r', '\r'), ('\u2028', '\u2029')]))),
             Some('\\') => {
-            	let e = parse_escape(parser);
+            	let e = try!(parse_escape(parser));
             	stack.push(e);
             }
             Some('|') => {
             	do_concat(&mut stack);
-                match stack.pop_opt() {
-                    Some(left) => {
-                        let right = parse_recursive(parser);
-                        stack.push(Alternate(~left, ~right));
-                        break;
-                    }
-                    None => parser.fail("Missing left operand for '|' operator.")
-                }
+                // An empty alternative matches the empty string
+                let left = match stack.pop_opt() {
+                    Some(left) => left,
+                    None => Empty
+                };
+                let right = try!(parse_recursive(parser));
+                stack.push(Alternate(~left, ~right));
+                break;
             }
             Some('*') => {
                 match stack.pop_opt() {
-                	Some(Star(_, _)) |
-                	Some(Question(_, _)) => parser.fail("Nothing to repeat."), // Would cause infinite loops
+                    Some(ref e) if !is_quantifiable(e) => return parser.fail(NothingToRepeat),
                     Some(e) => stack.push(Star(~e, Greedy)),
-                    None => parser.fail("Missing left operand for '*' operator.")
+                    None => return parser.fail(MissingOperand)
                 }
             }
             Some('+') => {
                 match stack.pop_opt() {
-                	Some(Star(_, _)) |
-                	Some(Question(_, _)) => parser.fail("Nothing to repeat."), // Would cause infinite loops
+                    Some(ref e) if !is_quantifiable(e) => return parser.fail(NothingToRepeat),
                     Some(e) => stack.push(Plus(~e, Greedy)),
-                    None => parser.fail("Missing left operand for '+' operator.")
+                    None => return parser.fail(MissingOperand)
                 }
             }
             Some('?') => {
@@ -389,33 +1176,50 @@ fn parse_recursive(parser: &mut Parser) -> Expression {
                 	Some(ExactRepetition(e, count, Greedy)) => stack.push(ExactRepetition(e, count, NonGreedy)),
                 	Some(UnboundedRepetition(e, low, Greedy)) => stack.push(UnboundedRepetition(e, low, NonGreedy)),
                 	Some(BoundedRepetition(e, low, high, Greedy)) => stack.push(BoundedRepetition(e, low, high, NonGreedy)),
+                    Some(ref e) if !is_quantifiable(e) => return parser.fail(NothingToRepeat),
                     Some(e) => stack.push(Question(~e, Greedy)),
-                    None => parser.fail("Missing left operand for '?' operator.")
+                    None => return parser.fail(MissingOperand)
                 }
             }
             Some('(') => {
                 do_concat(&mut stack);
-                let e = parse_group(parser);
-                stack.push(e);
+                if is_flag_group(parser) {
+                    match try!(parse_flag_group(parser)) {
+                        Some(e) => stack.push(e),
+                        None => ()
+                    }
+                } else {
+                    let e = try!(parse_group(parser));
+                    stack.push(e);
+                }
             }
             Some(')') => break,
-            Some('[') => stack.push(parse_charclass(parser)),
+            Some('[') => stack.push(try!(parse_charclass(parser))),
             Some('{') => {
             	match stack.pop_opt() {
-            		Some(e) => stack.push(parse_repetition(parser, e)),
-            		None => parser.fail("Unexpected '{' encountered.")
+            		Some(ref e) if !is_quantifiable(e) => return parser.fail(NothingToRepeat),
+            		Some(e) => stack.push(try!(parse_repetition(parser, e))),
+            		None => return parser.fail(UnexpectedOpenBrace)
             	}
             }
+            Some('^') if parser.flags.multiline => stack.push(AssertLineStart),
             Some('^') => stack.push(AssertStart),
+            Some('$') if parser.flags.multiline => stack.push(AssertLineEnd),
             Some('$') => stack.push(AssertEnd),
-            Some(c) => stack.push(Literal(c)),
+            Some(c) => {
+                let e = match parse_folded_sequence(parser, c) {
+                    Some(e) => e,
+                    None => parse_literal(parser, c)
+                };
+                stack.push(e);
+            }
             None => break
         }
     }
 
     do_concat(&mut stack);
     match stack.pop_opt() {
-    	Some(e) => return e,
-    	None => parser.fail("Illegal empty expression.")
+    	Some(e) => return Ok(e),
+    	None => Ok(Empty)
     }
 }
\ No newline at end of file
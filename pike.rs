@@ -7,30 +7,33 @@ use super::compile::{
     Char,
     Any,
     Range,
+    Class,
     Fork,
     Jump,
     ConditionalJumpEq,
     ConditionalJumpLE,
     Increment,
+    ResetRegister,
+    SavePosition,
+    AssertProgress,
     SaveStart,
     SaveEnd,
     AssertStart,
     AssertEnd,
+    AssertLineStart,
+    AssertLineEnd,
     AssertWordBoundary,
     AssertNonWordBoundary,
+    LookAhead,
+    NegativeLookAhead,
+    LookBehind,
+    NegativeLookBehind,
+    Backreference,
     Accept
 };
 
 use super::matcher;
-
-// Not very international, but this is the standard
-// http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.6
-fn is_word_char(c: char) -> bool {
-    (c >= 'a' && c <= 'z') ||
-    (c >= 'A' && c <= 'Z') ||
-    (c >= '0' && c <= '9') ||
-    c == '_'
-}
+use super::matcher::Matcher;
 
 /*
 struct Thread<'self> {
@@ -40,6 +43,42 @@ struct Thread<'self> {
     captures: &'self mut [Option<matcher::Match>]
 }
 */
+// A set of pcs with constant time insertion, lookup and clearing
+// http://research.swtch.com/sparse
+struct SparseSet {
+    dense: ~[uint],
+    sparse: ~[uint],
+    size: uint
+}
+
+impl SparseSet {
+    fn new(capacity: uint) -> SparseSet {
+        SparseSet {
+            dense: vec::from_elem(capacity, 0u),
+            sparse: vec::from_elem(capacity, 0u),
+            size: 0
+        }
+    }
+
+    #[inline]
+    fn contains(&self, value: uint) -> bool {
+        let i = self.sparse[value];
+        i < self.size && self.dense[i] == value
+    }
+
+    #[inline]
+    fn insert(&mut self, value: uint) {
+        self.dense[self.size] = value;
+        self.sparse[value] = self.size;
+        self.size += 1;
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.size = 0;
+    }
+}
+
 struct Thread {
     pc: uint,
     match_start: uint,
@@ -47,94 +86,235 @@ struct Thread {
     registers: ~[uint]
 }
 
+// The state a search works in. It only depends on the program, so it can be
+// kept to save allocating it again for further searches with the same one.
+pub struct Scratch {
+    priv threads: ~[~Thread],
+    priv next_threads: ~[~Thread],
+    priv registers: uint,
+    priv register_kinds: ~[compile::RegisterKind],
+    priv visited: SparseSet,
+    priv visited_registers: ~[~[~[uint]]],
+    priv look_around: Option<~Scratch> // For the sub programs of lookarounds, made on first use
+}
+
+impl Scratch {
+    pub fn new(code: &[Instruction]) -> Scratch {
+        let registers = compile::count_registers(code);
+        Scratch {
+            threads: vec::with_capacity(code.len()),
+            next_threads: vec::with_capacity(code.len()),
+            registers: registers,
+            register_kinds: compile::register_kinds(code),
+            visited: SparseSet::new(code.len()),
+            visited_registers: if registers > 0 { vec::from_elem(code.len(), ~[]) } else { ~[] },
+            look_around: None
+        }
+    }
+}
+
 pub struct PikeMatcher<'self> {
     priv code: &'self [Instruction],
     priv threads: ~[~Thread],
     priv next_threads: ~[~Thread],
-    priv input: &'self str,
+    priv input: &'self [char],
     priv sp: uint,
-    priv matched: Option<~[matcher::Match]>,
-    priv registers: uint
+    priv matched: Option<~[Option<matcher::Match>]>,
+    priv registers: uint,
+    priv captures: uint, // Capture slots per thread, 0 when only checking whether there is a match
+    priv register_kinds: ~[compile::RegisterKind],
+    // The pcs reached in the current step, and the register keys of the
+    // threads that reached them. A thread that reaches a pc after a thread
+    // with the same key has lower priority and the same future, so it's dropped.
+    priv visited: SparseSet,
+    priv visited_registers: ~[~[~[uint]]],
+    priv look_around: Option<~Scratch>,
+    priv reverse: bool, // Matches backwards from the start position, used for lookbehind
+    priv full: bool // Only accepts at the end of the input, for is_full_match
 }
 
 impl<'self> PikeMatcher<'self> {
-    pub fn do_match<'r>(code: &'r [Instruction], input: &'r str) -> Option<~[matcher::Match]> {
+    // Finds the first match starting at or after start, or only at start if anchored,
+    // with the given number of capture slots
+    pub fn search<'r>(code: &'r [Instruction], input: &'r [char], captures: uint, start: uint, anchored: bool) -> Option<~[Option<matcher::Match>]> {
+        let mut matcher = PikeMatcher::new(code, input, captures, false);
+
+        return matcher.run(0, start, anchored);
+    }
+
+    // Like search, but works in the scratch of an earlier search with the
+    // same program, and returns it for the next one
+    pub fn search_with<'r>(code: &'r [Instruction], input: &'r [char], captures: uint, start: uint, anchored: bool, scratch: Scratch) -> (Option<~[Option<matcher::Match>]>, Scratch) {
+        let mut matcher = PikeMatcher::with_scratch(code, input, captures, false, scratch);
+        let groups = matcher.run(0, start, anchored);
+
+        return (groups, matcher.into_scratch());
+    }
+
+    // Only checks whether there is a match. No captures are kept, and the
+    // search stops at the first Accept reached instead of the best match.
+    pub fn is_match<'r>(code: &'r [Instruction], input: &'r [char], start: uint, anchored: bool) -> bool {
+        let mut matcher = PikeMatcher::new(code, input, 0, false);
+
+        return matcher.run(0, start, anchored).is_some();
+    }
+
+    // Whether the program matches the whole input. Like is_match, but a thread
+    // that reaches Accept before the end fails instead of ending the search.
+    pub fn is_full_match<'r>(code: &'r [Instruction], input: &'r [char]) -> bool {
+        let mut matcher = PikeMatcher::new(code, input, 0, false);
+        matcher.full = true;
+
+        return matcher.run(0, 0, true).is_some();
+    }
+
+    fn new<'r>(code: &'r [Instruction], input: &'r [char], captures: uint, reverse: bool) -> PikeMatcher<'r> {
+        PikeMatcher::with_scratch(code, input, captures, reverse, Scratch::new(code))
+    }
+
+    fn with_scratch<'r>(code: &'r [Instruction], input: &'r [char], captures: uint, reverse: bool, scratch: Scratch) -> PikeMatcher<'r> {
+        let Scratch { threads, next_threads, registers, register_kinds, visited, visited_registers, look_around } = scratch;
         let mut matcher = PikeMatcher {
             code: code,
-            threads: vec::with_capacity(code.len()),
-            next_threads: vec::with_capacity(code.len()),
+            threads: threads,
+            next_threads: next_threads,
             input: input,
             sp: 0,
             matched: None,
-            registers: compile::count_registers(code)
+            registers: registers,
+            captures: captures,
+            register_kinds: register_kinds,
+            visited: visited,
+            visited_registers: visited_registers,
+            look_around: look_around,
+            reverse: reverse,
+            full: false
         };
-        
-        return matcher.run(input);
+        // An earlier search may have stopped with threads left
+        matcher.threads.clear();
+        matcher.next_threads.clear();
+        matcher.visited.clear();
+        return matcher;
     }
 
-    #[inline]
-    fn schedule_next(&mut self, mut thread: ~Thread) {
-        thread.pc += 1;
-        self.next_threads.push(thread);
+    fn into_scratch(self) -> Scratch {
+        let PikeMatcher { threads, next_threads, registers, register_kinds, visited, visited_registers, look_around, _ } = self;
+        Scratch {
+            threads: threads,
+            next_threads: next_threads,
+            registers: registers,
+            register_kinds: register_kinds,
+            visited: visited,
+            visited_registers: visited_registers,
+            look_around: look_around
+        }
     }
 
-    fn run(&mut self, input: &str) -> Option<~[matcher::Match]> {
-        for (sp, c) in input.iter().chain("\x03".iter()).enumerate() {
-            debug!("Input {}", c);
+    // Whether the thread is the first in this step to reach its pc with its
+    // register key. This makes the number of threads at most the size of
+    // the program times the number of keys, which doesn't depend on the input.
+    #[inline]
+    fn visit(&mut self, thread: &Thread) -> bool {
+        let pc = thread.pc;
+        if self.registers == 0 {
+            if self.visited.contains(pc) {
+                return false;
+            }
+            self.visited.insert(pc);
+            return true;
+        }
 
-            self.sp = sp;
+        let key = self.register_key(thread.registers);
+        if !self.visited.contains(pc) {
+            self.visited.insert(pc);
+            self.visited_registers[pc] = ~[key];
+            return true;
+        }
+        if self.visited_registers[pc].contains(&key) {
+            return false;
+        }
+        self.visited_registers[pc].push(key);
+        return true;
+    }
 
-            if self.matched.is_none() {
-                //debug!("\tAdd thread {}: {}", self.thread_id, sp);
-                self.next_threads.push(
-                    ~Thread {
-                        pc: 0,
-                        match_start: sp,
-                        captures: ~[None, ..10],
-                        registers: vec::from_elem(self.registers, 0u) });
+    // The registers as far as they decide the future of a thread. A saved
+    // position is only compared to later positions by AssertProgress, so
+    // all that matters is whether it's the current one, and counts from the
+    // limit on compare the same.
+    #[inline]
+    fn register_key(&self, registers: &[uint]) -> ~[uint] {
+        registers.iter().enumerate().map(|(i, &value)| {
+            match self.register_kinds[i] {
+                compile::Position => (value == self.sp) as uint,
+                compile::Counter(limit) if value > limit => limit,
+                compile::Counter(_) => value
             }
+        }).collect()
+    }
 
-            util::swap(&mut self.threads, &mut self.next_threads);
-            self.threads.reverse();
-            self.next_threads.clear();
+    #[inline]
+    fn schedule_next(&mut self, mut thread: ~Thread) {
+        thread.pc += 1;
+        self.next_threads.push(thread);
+    }
 
-            'threads: loop {
-                match self.threads.pop_opt() {
-                    Some(thread) => {
-                        match self.run_thread(thread, c) {
-                            Some(_) => break,
-                            None => continue
-                        }
-                    },
-                    None => break
-                };
-            }
-        }
+    // Runs the sub program at pc anchored at the current position, returning 
+    // its captures if it matches. If reverse is set the sub program is run 
+    // backwards, for lookbehind. The scratch is sized for the whole program,
+    // so every sub program can run in the same one.
+    #[inline]
+    fn look_around(&mut self, pc: uint, reverse: bool) -> Option<~[Option<matcher::Match>]> {
+        let scratch = match self.look_around.take() {
+            Some(scratch) => *scratch,
+            None => Scratch::new(self.code)
+        };
+        let mut matcher = PikeMatcher::with_scratch(self.code, self.input, self.captures, reverse, scratch);
+        let captures = matcher.run(pc, self.sp, true);
 
-        return self.matched.clone();
+        self.look_around = Some(~matcher.into_scratch());
+        return captures;
+    }
+
+    #[inline]
+    fn is_word_boundary(&self, unicode: bool) -> bool {
+        let a = self.sp > 0 && matcher::is_word_char(self.input[self.sp - 1], unicode);
+        let b = self.sp < self.input.len() && matcher::is_word_char(self.input[self.sp], unicode);
+        a != b
     }
 
+    // c is the character at the current position, or None at the end of the
+    // input, where only instructions that consume nothing can succeed
     #[inline]
-    fn run_thread(&mut self, mut thread: ~Thread, c: char) -> Option<~[matcher::Match]> {
+    fn run_thread(&mut self, mut thread: ~Thread, c: Option<char>) -> Option<~[Option<matcher::Match>]> {
         loop {
             //debug!("\tThread {}", thread.id);
             //debug_instruction!(self.code[thread.pc]);
-            
+
+            if !self.visit(&*thread) {
+                return None;
+            }
+
             match self.code[thread.pc] {
-                Char(ch) if c == ch => {
+                Char(ch) if c == Some(ch) => {
                     self.schedule_next(thread);
                     return None;
                 }
                 Char(_) => return None,
-                Any => {
+                Any if c.is_some() => {
                     self.schedule_next(thread);
                     return None;
                 }
-                Range(start, end) if c >= start && c <= end => {
+                Any => return None,
+                Range(start, end) if c.map_default(false, |&c| c >= start && c <= end) => {
                     self.schedule_next(thread);
                     return None;
                 }
                 Range(_, _) => return None,
+                Class(ref class) if c.map_default(false, |&c| class.contains(c)) => {
+                    self.schedule_next(thread);
+                    return None;
+                }
+                Class(_) => return None,
                 Fork(pc1, pc2) => {
                     self.threads.push(
                         ~Thread {
@@ -164,21 +344,42 @@ impl<'self> PikeMatcher<'self> {
                     thread.registers[register] += 1;
                     thread.pc += 1;
                 }
+                ResetRegister(register) => {
+                    thread.registers[register] = 0;
+                    thread.pc += 1;
+                }
+                SavePosition(register) => {
+                    thread.registers[register] = self.sp;
+                    thread.pc += 1;
+                }
+                // An optional loop iteration that matched the empty string
+                AssertProgress(register) => {
+                    if thread.registers[register] == self.sp {
+                        return None;
+                    } else {
+                        thread.pc += 1;
+                    }
+                }
+                SaveStart(_) |
+                SaveEnd(_) if self.captures == 0 => thread.pc += 1,
                 SaveStart(group) => {
                     thread.captures = thread.captures.clone();
 
-                    if group < thread.captures.len() {
-                        thread.captures[group] = Some(matcher::Match{ start: self.sp, end: self.sp });
-                    }
+                    thread.captures[group] = Some(matcher::Match{ start: self.sp, end: self.sp });
 
                     thread.pc +=1;
                 }
                 SaveEnd(group) => {
-                    if group < thread.captures.len() {
-                        match thread.captures[group] {
-                            Some(ref mut m) => m.end = self.sp,
-                            None => unreachable!()
+                    match thread.captures[group] {
+                        Some(ref mut m) => {
+                            // When matching backwards the end was saved first
+                            if self.reverse {
+                                m.start = self.sp;
+                            } else {
+                                m.end = self.sp;
+                            }
                         }
+                        None => unreachable!()
                     }
 
                     thread.pc +=1;
@@ -191,58 +392,151 @@ impl<'self> PikeMatcher<'self> {
                     }
                 }
                 AssertEnd => {
-                    if self.sp == self.input.char_len() {
+                    if self.sp == self.input.len() {
                         thread.pc += 1;
                     } else {
                         return None;
                     }
                 }
-                AssertWordBoundary => {
-                    let a = if self.sp == 0 {
-                        false
+                AssertLineStart => {
+                    if self.sp == 0 || matcher::is_line_terminator(self.input[self.sp - 1]) {
+                        thread.pc += 1;
                     } else {
-                        // TODO: char indexing
-                        is_word_char(self.input[self.sp - 1] as char)
-                    };
-                    let b = is_word_char(c);
-
-                    if (a && !b) || (!a && b) {
+                        return None;
+                    }
+                }
+                AssertLineEnd => {
+                    if self.sp == self.input.len() || matcher::is_line_terminator(self.input[self.sp]) {
                         thread.pc += 1;
                     } else {
                         return None;
                     }
                 }
-                AssertNonWordBoundary => {
-                    let a = if self.sp == 0 {
-                        false
+                AssertWordBoundary(unicode) => {
+                    if self.is_word_boundary(unicode) {
+                        thread.pc += 1;
                     } else {
-                        // TODO: char indexing
-                        is_word_char(self.input[self.sp - 1] as char)
-                    };
-                    let b = is_word_char(c);
-                    
-                    if (a && !b) || (!a && b) {
+                        return None;
+                    }
+                }
+                AssertNonWordBoundary(unicode) => {
+                    if self.is_word_boundary(unicode) {
                         return None;
                     } else {
                         thread.pc += 1;
                     }
                 }
-                Accept => {
-                    let mut matches = ~[];
-                    matches.push(matcher::Match{ start: thread.match_start, end: self.sp });
-                    for capture in thread.captures.iter() {
-                        match capture {
-                            &Some(m) => matches.push(m),
-                            &None => ()
+                LookAhead(next_pc) |
+                LookBehind(next_pc) => {
+                    let reverse = match self.code[thread.pc] { LookBehind(_) => true, _ => false };
+                    match self.look_around(thread.pc + 1, reverse) {
+                        Some(captures) => {
+                            // Captures made inside the assertion are kept
+                            for (group, capture) in captures.iter().enumerate().skip(1) {
+                                if capture.is_some() {
+                                    thread.captures[group] = *capture;
+                                }
+                            }
+                            thread.pc = next_pc;
                         }
+                        None => return None
+                    }
+                }
+                NegativeLookAhead(next_pc) |
+                NegativeLookBehind(next_pc) => {
+                    let reverse = match self.code[thread.pc] { NegativeLookBehind(_) => true, _ => false };
+                    match self.look_around(thread.pc + 1, reverse) {
+                        Some(_) => return None,
+                        None => thread.pc = next_pc
                     }
-                    self.matched = Some(matches);
+                }
+                // Regex runs programs with backreferences on the backtracking
+                // matcher, since threads here don't carry their own captures
+                // in ECMA-262 order
+                Backreference(_, _) => unreachable!(),
+                Accept if self.full && self.sp != self.input.len() => return None,
+                Accept if self.captures == 0 => {
+                    self.matched = Some(~[]);
+                    return self.matched.clone();
+                }
+                Accept => {
+                    let mut captures = thread.captures.clone();
+                    captures[0] = if self.reverse {
+                        Some(matcher::Match{ start: self.sp, end: thread.match_start })
+                    } else {
+                        Some(matcher::Match{ start: thread.match_start, end: self.sp })
+                    };
+                    self.matched = Some(captures);
                     return self.matched.clone();
                 }
             }
         }
     }
 }
+
+impl<'self> Matcher for PikeMatcher<'self> {
+    fn run(&mut self, start_pc: uint, start_sp: uint, anchored: bool) -> Option<~[Option<matcher::Match>]> {
+        let len = self.input.len();
+        let mut sp = start_sp;
+
+        loop {
+            let c = if self.reverse {
+                if sp > 0 { Some(self.input[sp - 1]) } else { None }
+            } else {
+                if sp < len { Some(self.input[sp]) } else { None }
+            };
+            debug!("Input {:?}", c);
+
+            self.sp = sp;
+
+            if self.matched.is_none() && (!anchored || sp == start_sp) {
+                //debug!("\tAdd thread {}: {}", self.thread_id, sp);
+                self.next_threads.push(
+                    ~Thread {
+                        pc: start_pc,
+                        match_start: sp,
+                        captures: vec::from_elem(self.captures, None),
+                        registers: vec::from_elem(self.registers, 0u) });
+            }
+
+            if self.next_threads.is_empty() && (anchored || self.matched.is_some()) {
+                break;
+            }
+
+            util::swap(&mut self.threads, &mut self.next_threads);
+            self.threads.reverse();
+            self.next_threads.clear();
+            self.visited.clear();
+
+            'threads: loop {
+                match self.threads.pop_opt() {
+                    Some(thread) => {
+                        match self.run_thread(thread, c) {
+                            Some(_) => break,
+                            None => continue
+                        }
+                    },
+                    None => break
+                };
+            }
+
+            if self.captures == 0 && self.matched.is_some() {
+                break;
+            }
+
+            if self.reverse {
+                if sp == 0 { break; }
+                sp -= 1;
+            } else {
+                if sp == len { break; }
+                sp += 1;
+            }
+        }
+
+        return self.matched.clone();
+    }
+}
+
 /*
 macro_rules! debug_instruction!(instruction: Instruction) {
     match instruction {
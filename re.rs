@@ -2,29 +2,183 @@ extern mod extra;
 
 //use std::str;
 use std::os;
+use std::hashmap::HashMap;
 use std::util;
 use std::vec;
 
 use compile::Instruction;
+use parse::{ParseError, Flags};
 
 mod parse;
+mod case;
+mod unicode;
+mod charclass;
 mod compile;
 mod matcher;
+mod captures;
+mod matches;
 mod pike;
+mod backtrack;
 mod debug;
 
 pub struct Regex {
-    priv code: ~[Instruction]
+    priv code: ~[Instruction],
+    priv names: HashMap<~str, uint>,
+    priv captures: uint, // Capture slots, including 0 for the full match
+    priv backtrack: bool // Backreferences need the backtracking matcher
 }
 
 impl Regex {
-    fn new(pattern: &str) -> ~Regex {
-        let etree = parse::parse(pattern);
-        let code = compile::compile(&etree);
-        return ~Regex { code: code };
+    fn new(pattern: &str) -> Result<~Regex, ParseError> {
+        Regex::with_flags(pattern, Flags::new())
+    }
+    fn with_flags(pattern: &str, flags: Flags) -> Result<~Regex, ParseError> {
+        Regex::with_unroll_limit(pattern, flags, compile::DEFAULT_UNROLL_LIMIT)
+    }
+    // Counted repetitions up to unroll_limit instructions are compiled to
+    // copies of their expression, larger ones to loops with a counter
+    fn with_unroll_limit(pattern: &str, flags: Flags, unroll_limit: uint) -> Result<~Regex, ParseError> {
+        let etree = match parse::parse(pattern, flags) {
+            Ok(e) => e,
+            Err(e) => return Err(e)
+        };
+        let code = compile::compile(&etree, unroll_limit);
+        let backtrack = matcher::uses_backreferences(code);
+        return Ok(~Regex {
+            code: code,
+            names: parse::capture_names(&etree),
+            captures: parse::count_captures(&etree),
+            backtrack: backtrack
+        });
+    }
+    // Whether the pattern matches anywhere in the input. Faster than the other
+    // methods, since it doesn't have to find out where.
+    fn is_match(&self, input: &str) -> bool {
+        let chars: ~[char] = input.iter().collect();
+        if self.backtrack {
+            self.search(chars, 0, false).is_some()
+        } else {
+            pike::PikeMatcher::is_match(self.code, chars, 0, false)
+        }
+    }
+    // Whether the pattern matches the whole input, like fullmatch in Python
+    fn is_full_match(&self, input: &str) -> bool {
+        let chars: ~[char] = input.iter().collect();
+        if self.backtrack {
+            backtrack::BacktrackMatcher::is_full_match(self.code, chars, self.captures)
+        } else {
+            pike::PikeMatcher::is_full_match(self.code, chars)
+        }
     }
     fn partial_match(&self, input: &str) -> Option<~[matcher::Match]> {
-        pike::PikeMatcher::do_match(self.code, input)
+        let chars: ~[char] = input.iter().collect();
+        matcher::compact(self.search(chars, 0, false))
+    }
+    // Like partial_match, but with every group at its own index, and the full
+    // match at 0, as byte offsets into the input
+    fn captures<'t>(&'t self, input: &'t str) -> Option<captures::Captures<'t>> {
+        let chars: ~[char] = input.iter().collect();
+        match self.search(chars, 0, false) {
+            Some(groups) => Some(captures::Captures::new(input, captures::byte_offsets(input), groups, &self.names)),
+            None => None
+        }
+    }
+    // The full match, as byte offsets into the input
+    fn find<'t>(&'t self, input: &'t str) -> Option<captures::Match<'t>> {
+        match self.captures(input) {
+            Some(captures) => captures.get(0),
+            None => None
+        }
+    }
+    // The first match starting at or after the byte offset start. Assertions
+    // and lookbehind still see the text before it. None if start is not at
+    // a character boundary of the input.
+    fn find_at<'t>(&'t self, input: &'t str, start: uint) -> Option<captures::Match<'t>> {
+        let chars: ~[char] = input.iter().collect();
+        let offsets = captures::byte_offsets(input);
+        let sp = match offsets.bsearch_elem(&start) {
+            Some(sp) => sp,
+            None => return None
+        };
+        match self.search(chars, sp, false) {
+            Some(groups) => {
+                let m = groups[0].unwrap();
+                Some(captures::Match::new(input, offsets[m.start], offsets[m.end]))
+            }
+            None => None
+        }
+    }
+    // Every non-overlapping match, from left to right
+    fn find_iter<'t>(&'t self, input: &'t str) -> matches::FindMatches<'t> {
+        matches::FindMatches::new(self, input)
+    }
+    // The captures of every non-overlapping match, from left to right
+    fn captures_iter<'t>(&'t self, input: &'t str) -> matches::FindCaptures<'t> {
+        matches::FindCaptures::new(self, input)
+    }
+    // The text between the matches
+    fn split<'t>(&'t self, input: &'t str) -> matches::Split<'t> {
+        matches::Split::new(self, input)
+    }
+    // The text between the matches, in at most limit pieces
+    fn splitn<'t>(&'t self, input: &'t str, limit: uint) -> matches::SplitN<'t> {
+        matches::SplitN::new(self, input, limit)
+    }
+    // Splits like String.prototype.split in ECMA-262, with the groups of the
+    // separators between the pieces
+    fn split_captures<'t>(&'t self, input: &'t str, limit: Option<uint>) -> ~[Option<&'t str>] {
+        matches::split_captures(self, input, limit)
+    }
+    // Replaces the first match with the template, see Captures::expand
+    fn replace(&self, input: &str, template: &str) -> ~str {
+        self.replacen(input, 1, template)
+    }
+    // Replaces every match with the template
+    fn replace_all(&self, input: &str, template: &str) -> ~str {
+        self.replacen(input, 0, template)
+    }
+    // Replaces the first limit matches with the template, or all of them if limit is 0
+    fn replacen(&self, input: &str, limit: uint, template: &str) -> ~str {
+        self.replacen_with(input, limit, |captures| captures.expand(template))
+    }
+    // Like replace, with the replacement computed from the captures
+    fn replace_with(&self, input: &str, replacer: &fn(&captures::Captures) -> ~str) -> ~str {
+        self.replacen_with(input, 1, replacer)
+    }
+    fn replace_all_with(&self, input: &str, replacer: &fn(&captures::Captures) -> ~str) -> ~str {
+        self.replacen_with(input, 0, replacer)
+    }
+    fn replacen_with(&self, input: &str, limit: uint, replacer: &fn(&captures::Captures) -> ~str) -> ~str {
+        let mut result = ~"";
+        let mut last = 0;
+
+        for (i, captures) in self.captures_iter(input).enumerate() {
+            if limit > 0 && i == limit {
+                break;
+            }
+            let (start, end) = captures.get(0).unwrap().range();
+            result.push_str(input.slice(last, start));
+            result.push_str(replacer(&captures));
+            last = end;
+        }
+        result.push_str(input.slice_from(last));
+
+        return result;
+    }
+    // The groups of the first match at or after start, or only at start if
+    // anchored, as character indices
+    fn search(&self, chars: &[char], start: uint, anchored: bool) -> Option<~[Option<matcher::Match>]> {
+        if self.backtrack {
+            backtrack::BacktrackMatcher::search(self.code, chars, self.captures, start, anchored)
+        } else {
+            pike::PikeMatcher::search(self.code, chars, self.captures, start, anchored)
+        }
+    }
+    fn group_index(&self, name: &str) -> Option<uint> {
+        match self.names.find_equiv(&name) {
+            Some(&index) => Some(index),
+            None => None
+        }
     }
 }
 
@@ -32,22 +186,31 @@ fn main()  {
     match os::args() {
         [_, pattern, input] => {
 
-            let etree = parse::parse(pattern);
+            let etree = match parse::parse(pattern, Flags::new()) {
+                Ok(e) => e,
+                Err(e) => {
+                    debug::print_parse_error(pattern, &e);
+                    return;
+                }
+            };
             println("\nExpression Tree");
             println("-----------------");
             debug::print_expression_tree(&etree);
 
-            let code = compile::compile(&etree);
+            let code = compile::compile(&etree, compile::DEFAULT_UNROLL_LIMIT);
             println("\nCode");
             println("------");
             debug::print_code(code);
 
-            let re = Regex::new(pattern);
-            match re.partial_match(input) {
-                Some(matches) => {
+            let re = Regex::new(pattern).unwrap();
+            match re.captures(input) {
+                Some(captures) => {
                     println("\nYay!");
-                    for (i, m) in matches.iter().enumerate() {
-                        println!("  {}: {} ({}, {})", i, input.slice_chars(m.start, m.end), m.start, m.end);
+                    for (i, group) in captures.iter().enumerate() {
+                        match group {
+                            Some(m) => println!("  {}: {} ({}, {})", i, m.as_str(), m.start(), m.end()),
+                            None => println!("  {}: unmatched", i)
+                        }
                     }
                     println("");
                 }
@@ -62,8 +225,12 @@ fn main()  {
 #[cfg(test)]
 mod test {
     use std::task;
+    use std::from_str;
 
     use super::Regex;
+    use super::parse;
+    use super::case;
+    use super::compile;
     // Apparently this can't be done... ?
     //use tests::{TestResult, NoMatch, ParseError, TestCases};
 
@@ -74,13 +241,26 @@ mod test {
     fn test_all_the_things() {
         let mut errors = 0u;
         for &(pattern, input, result, expected_match, expected_captures) in tests::TestCases.iter() {
+            let re = match Regex::new(pattern) {
+                Ok(re) => re,
+                Err(e) => {
+                    match result {
+                        tests::Match |
+                        tests::NoMatch => {
+                            println("\tFAIL: Test pattern \"" + pattern + "\" on \"" + input + "\" failed. " + e.to_str() + "\n");
+                            errors += 1;
+                        }
+                        tests::ParseError => ()
+                    }
+                    continue;
+                }
+            };
             let task_result = do task::try {
-                let re = Regex::new(pattern);
-                match re.partial_match(input) {
-                    Some(matches) => {
-                        let m = matches.head();
-                        let captures = matches.tail();
-                        let actual_match = input.slice_chars(m.start, m.end);
+                match re.captures(input) {
+                    Some(groups) => {
+                        let actual_match = groups[0];
+                        // Only the groups that matched
+                        let captures: ~[&str] = groups.iter().skip(1).filter_map(|m| m.map_move(|m| m.as_str())).collect();
 
                         match result {
                             tests::Match => {
@@ -94,9 +274,8 @@ mod test {
                                     println!("Expected {} captures. Got {}", expected_captures.len(), captures.len())
                                     match_error = true;
                                 } else {
-                                    for (i, m) in captures.iter().enumerate() {
-                                        let capture = input.slice_chars(m.start, m.end).to_owned();
-                                        if capture != expected_captures[i].to_owned() {
+                                    for (i, &capture) in captures.iter().enumerate() {
+                                        if capture != expected_captures[i] {
                                             println!("Expected capture {} to be \"{}\". Got \"{}\"", i, expected_captures[i], capture);
                                             match_error = true;
                                         }
@@ -119,16 +298,9 @@ mod test {
             };
             match task_result {
                 Ok(_) => (),
-                Err(_) => match result {
-                    tests::Match |
-                    tests::NoMatch => {
-                        println("\tFAIL: Test pattern \"" + pattern + "\" on \"" + input + "\" failed.\n");
-                        errors += 1;
-                    }
-                    tests::ParseError => {
-                        // Currently unable to distinguish between expected parse errors and other errors.
-                        //println("\tTest pattern \"" + pattern + "\" on \"" + input + "\" failed. Expected parse error.\n");  
-                    }
+                Err(_) => {
+                    println("\tFAIL: Test pattern \"" + pattern + "\" on \"" + input + "\" failed.\n");
+                    errors += 1;
                 }
             }
         }
@@ -138,6 +310,539 @@ mod test {
 
         println!("{} tests PASSED", tests::TestCases.len());
     }
+
+    #[test]
+    fn test_named_groups() {
+        let re = Regex::new("(?<year>\\d{4})-(?P<month>\\d\\d)(?:-(?<day>\\d\\d))?").unwrap();
+        assert_eq!(re.group_index("year"), Some(1));
+        assert_eq!(re.group_index("month"), Some(2));
+        assert_eq!(re.group_index("day"), Some(3));
+        assert_eq!(re.group_index("hour"), None);
+
+        let input = "on 2013-10";
+        let captures = re.captures(input).unwrap();
+        assert_eq!(captures.name("year").unwrap().as_str(), "2013");
+        assert_eq!(captures.name("month").unwrap().as_str(), "10");
+        assert!(captures.name("day").is_none());
+        assert!(captures.name("hour").is_none());
+    }
+
+    #[test]
+    fn test_byte_offsets() {
+        let re = Regex::new("(?<word>\\S+) (\\d+)").unwrap();
+        let input = "\u00e5r \u00fcber 42";
+        let m = re.find(input).unwrap();
+        assert_eq!(m.range(), (4, 12));
+        assert_eq!(m.char_range(), (3, 10));
+        assert_eq!(m.as_str(), "\u00fcber 42");
+        assert_eq!(input.slice(m.start(), m.end()), m.as_str());
+
+        let captures = re.captures(input).unwrap();
+        assert_eq!(captures[0], "\u00fcber 42");
+        assert_eq!(captures[1], "\u00fcber");
+        assert_eq!(captures.name("word").unwrap().range(), (4, 9));
+        assert_eq!(captures.get(2).unwrap().as_str(), "42");
+
+        let groups: ~[&str] = captures.iter().map(|m| m.unwrap().as_str()).collect();
+        assert_eq!(groups, ~["\u00fcber 42", "\u00fcber", "42"]);
+
+        assert!(re.find("none").is_none());
+    }
+
+    #[test]
+    fn test_is_match() {
+        for &(pattern, input, result, _, _) in tests::TestCases.iter() {
+            match (Regex::new(pattern), result) {
+                (Ok(re), tests::Match) => assert!(re.is_match(input)),
+                (Ok(re), tests::NoMatch) => assert!(!re.is_match(input)),
+                _ => ()
+            }
+        }
+    }
+
+    #[test]
+    fn test_full_match() {
+        let re = Regex::new("a|ab").unwrap();
+        assert!(re.is_full_match("ab"));
+        assert!(re.is_full_match("a"));
+        assert!(!re.is_full_match("abc"));
+        assert!(!re.is_full_match("xab"));
+
+        let re = Regex::new("(\\w+)\\s\\1").unwrap();
+        assert!(re.is_full_match("ab ab"));
+        assert!(!re.is_full_match("ab abc"));
+        let re = Regex::new("(a)|(a)\\2").unwrap();
+        assert!(re.is_full_match("aa"));
+
+        let re = Regex::new("\\d*").unwrap();
+        assert!(re.is_full_match(""));
+        assert!(!re.is_full_match("1a"));
+
+        // Lookahead may still look past the end of the match
+        let re = Regex::new("a(?=b)|ab").unwrap();
+        assert!(re.is_full_match("ab"));
+    }
+
+    #[test]
+    fn test_find_at() {
+        let re = Regex::new("\\b\\w+").unwrap();
+        let input = "\u00e9t\u00e9 word";
+        assert_eq!(re.find_at(input, 0).unwrap().as_str(), "t");
+        assert_eq!(re.find_at(input, 2).unwrap().as_str(), "t");
+        assert_eq!(re.find_at(input, 3).unwrap().as_str(), "word");
+        // Not a word boundary, since the preceding character is seen
+        assert!(re.find_at(input, 7).is_none());
+        assert!(re.find_at(input, input.len()).is_none());
+
+        let re = Regex::new("^a").unwrap();
+        assert!(re.find_at("aa", 1).is_none());
+        let re = Regex::new("(?<=a)b").unwrap();
+        assert_eq!(re.find_at("ab", 1).unwrap().range(), (1, 2));
+    }
+
+    #[test]
+    fn test_find_at_inside_character() {
+        let re = Regex::new("a").unwrap();
+        assert!(re.find_at("\u00e9a", 1).is_none());
+        assert!(re.find_at("\u00e9a", 4).is_none());
+        assert_eq!(re.find_at("\u00e9a", 2).unwrap().range(), (2, 3));
+    }
+
+    #[test]
+    fn test_thread_deduplication() {
+        // Would need a thread for every way to match the a's without it
+        let input = "a".repeat(100);
+        let re = Regex::new("(a|a)*b").unwrap();
+        assert!(re.partial_match(input).is_none());
+        let re = Regex::new("(?:a|aa)*c").unwrap();
+        assert!(!re.is_match(input));
+        let re = Regex::new("(a*)*b").unwrap();
+        assert!(re.partial_match(input).is_none());
+
+        // Saved positions only tell whether an iteration made progress, so
+        // loops over nullable bodies stay linear on long inputs
+        let input = "a".repeat(20000);
+        let re = Regex::new("(a*)*b").unwrap();
+        assert!(re.partial_match(input).is_none());
+        let re = Regex::new("(?:a|)+(?:a?)*c").unwrap();
+        assert!(!re.is_match(input));
+
+        // The thread with the highest priority survives
+        let re = Regex::new("(a|a)*").unwrap();
+        let captures = re.captures("aaa").unwrap();
+        assert_eq!(captures.get(1).unwrap().range(), (2, 3));
+        let re = Regex::new("(?:(a)|(a))+?$").unwrap();
+        let captures = re.captures("aa").unwrap();
+        assert!(captures.get(1).is_some());
+        assert!(captures.get(2).is_none());
+    }
+
+    #[test]
+    fn test_unrolled_repetition() {
+        // Without registers threads at the same pc are the same, so the Pike
+        // VM keeps at most one thread per instruction
+        for &pattern in ["\\d{1,3}", "(?:a|b){2}", "a{3,}?", "(?:\\d{1,3}\\.){3}\\d{1,3}"].iter() {
+            let etree = parse::parse(pattern, parse::Flags::new()).unwrap();
+            assert_eq!(compile::count_registers(compile::compile(&etree, 1000)), 0);
+        }
+
+        // Beyond the limit the iterations are counted instead
+        let etree = parse::parse("a{3}", parse::Flags::new()).unwrap();
+        assert_eq!(compile::compile(&etree, 1000).len(), 4);
+        assert_eq!(compile::count_registers(compile::compile(&etree, 3)), 1);
+
+        let input = "1".repeat(100);
+        let re = Regex::new("(?:\\d{1,3}){1,40}x").unwrap();
+        assert!(!re.is_match(input));
+
+        // Every body is compiled once, however deeply repetitions are nested
+        let pattern = "(?:".repeat(40) + "a" + "){1}".repeat(40);
+        assert!(Regex::new(pattern).unwrap().is_match("a"));
+        let pattern = "(?:".repeat(40) + "a?" + ")+".repeat(40);
+        assert!(Regex::new(pattern).unwrap().is_match("a"));
+    }
+
+    #[test]
+    fn test_counted_repetition() {
+        // Every pattern again, with none of the repetitions unrolled
+        for &(pattern, input, result, expected_match, _) in tests::TestCases.iter() {
+            match (Regex::with_unroll_limit(pattern, parse::Flags::new(), 0), result) {
+                (Ok(re), tests::Match) => assert_eq!(re.find(input).unwrap().as_str(), expected_match),
+                (Ok(re), tests::NoMatch) => assert!(re.find(input).is_none()),
+                _ => ()
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let re = Regex::new("\\d+").unwrap();
+        let matches: ~[&str] = re.find_iter("a1 22 \u00e9333").map(|m| m.as_str()).collect();
+        assert_eq!(matches, ~["1", "22", "333"]);
+
+        // Empty matches move on by one character, never inside a UTF-8 sequence
+        let re = Regex::new("a*").unwrap();
+        let ranges: ~[(uint, uint)] = re.find_iter("\u00e9aa\u00e9").map(|m| m.range()).collect();
+        assert_eq!(ranges, ~[(0, 0), (2, 4), (4, 4), (6, 6)]);
+
+        let re = Regex::new("").unwrap();
+        let ranges: ~[(uint, uint)] = re.find_iter("").map(|m| m.range()).collect();
+        assert_eq!(ranges, ~[(0, 0)]);
+        let ranges: ~[(uint, uint)] = re.find_iter("\u00e9\u00e9").map(|m| m.range()).collect();
+        assert_eq!(ranges, ~[(0, 0), (2, 2), (4, 4)]);
+
+        let re = Regex::new("x").unwrap();
+        assert!(re.find_iter("abc").next().is_none());
+
+        // Lookbehind still sees the text before the next search
+        let re = Regex::new("(?<=a)b").unwrap();
+        let ranges: ~[(uint, uint)] = re.find_iter("abbab").map(|m| m.range()).collect();
+        assert_eq!(ranges, ~[(1, 2), (4, 5)]);
+    }
+
+    #[test]
+    fn test_captures_iter() {
+        let re = Regex::new("(?<key>\\w+)=(\\w+)?").unwrap();
+        let mut pairs = ~[];
+        for captures in re.captures_iter("a=1 b= c=3") {
+            pairs.push((captures.name("key").unwrap().as_str(), captures.get(2).map_move(|m| m.as_str())));
+        }
+        assert_eq!(pairs, ~[("a", Some("1")), ("b", None), ("c", Some("3"))]);
+
+        // Each search starts afresh in the scratch of the one before
+        let re = Regex::new("(a)|(b)|(?:c|d?)+").unwrap();
+        let groups: ~[(bool, bool)] = re.captures_iter("abcc").map(|c| (c.get(1).is_some(), c.get(2).is_some())).collect();
+        assert_eq!(groups, ~[(true, false), (false, true), (false, false), (false, false)]);
+
+        // With the backtracking matcher
+        let re = Regex::new("(\\w)\\1").unwrap();
+        let doubles: ~[&str] = re.captures_iter("aabccdd").map(|c| c[1]).collect();
+        assert_eq!(doubles, ~["a", "c", "d"]);
+    }
+
+    #[test]
+    fn test_look_around_scratch() {
+        // Every lookahead runs in the scratch of the one before
+        let re = Regex::new("(?=(\\w)(\\w))\\w").unwrap();
+        let pairs: ~[(&str, &str)] = re.captures_iter("abc").map(|c| (c[1], c[2])).collect();
+        assert_eq!(pairs, ~[("a", "b"), ("b", "c")]);
+
+        // Nested ones in a scratch of their own
+        let re = Regex::new("(?=a(?!b))\\w").unwrap();
+        assert_eq!(re.find("abac").unwrap().range(), (2, 3));
+        let re = Regex::new("^(?:(?=a)a)+$").unwrap();
+        assert!(re.is_match("a".repeat(1000)));
+
+        // Lookbehind runs backwards in the same scratch
+        let re = Regex::new("(?<=(\\w)(\\w))\\w").unwrap();
+        let pairs: ~[(&str, &str)] = re.captures_iter("abcd").map(|c| (c[1], c[2])).collect();
+        assert_eq!(pairs, ~[("a", "b"), ("b", "c")]);
+        let re = Regex::new("(?<=(?<!x)a)b").unwrap();
+        assert_eq!(re.find("xabab").unwrap().range(), (4, 5));
+        let re = Regex::new("(?<=a)b(?=c)|(?<=b)c(?!d)").unwrap();
+        let ranges: ~[(uint, uint)] = re.find_iter("abcabd").map(|m| m.range()).collect();
+        assert_eq!(ranges, ~[(1, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn test_replace() {
+        let re = Regex::new("(\\w+)@(?<host>\\w+)").unwrap();
+        let input = "a@x, b@y";
+        assert_eq!(re.replace(input, "$2:$1"), ~"x:a, b@y");
+        assert_eq!(re.replace_all(input, "${host}/${1}"), ~"x/a, y/b");
+        assert_eq!(re.replacen(input, 2, "[$&]"), ~"[a@x], [b@y]");
+        assert_eq!(re.replace_all(input, "<$`|$'>"), ~"<|, b@y>, <a@x, |>");
+        assert_eq!(re.replace_all(input, "$$1 $3 $0 ${nope} $"), ~"$1 $3 $0 ${nope} $, $1 $3 $0 ${nope} $");
+        assert_eq!(re.replace_all("none", "$1"), ~"none");
+
+        // Two digit groups when they exist, otherwise one digit and a literal
+        let re = Regex::new("(a)(b)?").unwrap();
+        assert_eq!(re.replace("a", "[$2][$10][$01]"), ~"[][a0][a]");
+
+        let re = Regex::new("\\d+").unwrap();
+        let doubled = re.replace_all_with("1 22 x", |captures| {
+            let n: uint = from_str::from_str(captures.get(0).unwrap().as_str()).unwrap();
+            (n * 2).to_str()
+        });
+        assert_eq!(doubled, ~"2 44 x");
+        assert_eq!(re.replace_with("1 22", |_| ~"n"), ~"n 22");
+
+        let re = Regex::new("x*").unwrap();
+        assert_eq!(re.replace_all("abc", "-"), ~"-a-b-c-");
+    }
+
+    #[test]
+    fn test_split() {
+        let re = Regex::new("\\s*,\\s*").unwrap();
+        let pieces: ~[&str] = re.split("a, b ,c,").collect();
+        assert_eq!(pieces, ~["a", "b", "c", ""]);
+        let pieces: ~[&str] = re.split("").collect();
+        assert_eq!(pieces, ~[""]);
+
+        let pieces: ~[&str] = re.splitn("a, b ,c,", 2).collect();
+        assert_eq!(pieces, ~["a", "b ,c,"]);
+        let pieces: ~[&str] = re.splitn("a, b", 5).collect();
+        assert_eq!(pieces, ~["a", "b"]);
+        assert!(re.splitn("a, b", 0).next().is_none());
+
+        let re = Regex::new("").unwrap();
+        let pieces: ~[&str] = re.split("\u00e9a").collect();
+        assert_eq!(pieces, ~["", "\u00e9", "a", ""]);
+    }
+
+    #[test]
+    fn test_split_captures() {
+        // The examples from the ECMA-262 specification
+        let re = Regex::new("a*?").unwrap();
+        assert_eq!(re.split_captures("ab", None), ~[Some("a"), Some("b")]);
+        let re = Regex::new("a*").unwrap();
+        assert_eq!(re.split_captures("ab", None), ~[Some(""), Some("b")]);
+        let re = Regex::new("<(\\/)?([^<>]+)>").unwrap();
+        assert_eq!(re.split_captures("A<B>bold</B>and<CODE>coded</CODE>", None),
+            ~[Some("A"), None, Some("B"), Some("bold"), Some("/"), Some("B"), Some("and"), None,
+              Some("CODE"), Some("coded"), Some("/"), Some("CODE"), Some("")]);
+
+        let re = Regex::new("(-)").unwrap();
+        assert_eq!(re.split_captures("a-b-c", Some(4)), ~[Some("a"), Some("-"), Some("b"), Some("-")]);
+        assert_eq!(re.split_captures("a-b", Some(0)), ~[]);
+
+        // The empty string is split only if the separator can't match it
+        assert_eq!(re.split_captures("", None), ~[Some("")]);
+        let re = Regex::new("x*").unwrap();
+        assert_eq!(re.split_captures("", None), ~[]);
+        assert_eq!(re.split_captures("\u00e9x\u00e9", None), ~[Some("\u00e9"), Some("\u00e9")]);
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_index_unmatched_group() {
+        let re = Regex::new("(a)|(b)").unwrap();
+        let captures = re.captures("b").unwrap();
+        let _ = captures[1];
+    }
+
+    #[test]
+    fn test_unmatched_groups() {
+        let re = Regex::new("(a)|(b)").unwrap();
+        let captures = re.captures("b").unwrap();
+        assert_eq!(captures.len(), 3);
+        assert!(captures.get(1).is_none());
+        assert_eq!(captures.get(2).unwrap().range(), (0, 1));
+        assert!(captures.get(3).is_none());
+
+        // The same with the backtracking matcher
+        let re = Regex::new("(?:(x)|(y))(z)?\\2").unwrap();
+        let captures = re.captures("yy").unwrap();
+        assert_eq!(captures.len(), 4);
+        assert!(captures.get(1).is_none());
+        assert!(captures.get(2).is_some());
+        assert!(captures.get(3).is_none());
+
+        // A group that is never compiled still exists
+        let re = Regex::new("(b)(a){0}").unwrap();
+        let captures = re.captures("b").unwrap();
+        assert_eq!(captures.len(), 3);
+        assert!(captures.get(2).is_none());
+        assert_eq!(captures.expand("[$1$2]"), ~"[b]");
+    }
+
+    #[test]
+    fn test_many_groups() {
+        let pattern = "(\\w)".repeat(40);
+        let re = Regex::new(pattern).unwrap();
+        let input = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMN";
+        let captures = re.captures(input).unwrap();
+        assert_eq!(captures.len(), 41);
+        assert_eq!(captures[40], "N");
+
+        let re = Regex::new("(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)(l)\\11").unwrap();
+        assert!(re.partial_match("abcdefghijklk").is_some());
+        assert!(re.partial_match("abcdefghijkla1").is_none());
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let mut flags = parse::Flags::new();
+        flags.ignore_case = true;
+        let re = Regex::with_flags("ab[c-e]", flags).unwrap();
+        assert!(re.partial_match("xABD").is_some());
+
+        let re = Regex::with_flags("a(?-i)b", flags).unwrap();
+        assert!(re.partial_match("Ab").is_some());
+        assert!(re.partial_match("AB").is_none());
+
+        let re = Regex::new("a(?i:b)c").unwrap();
+        assert!(re.partial_match("aBc").is_some());
+        assert!(re.partial_match("aBC").is_none());
+
+        let re = Regex::new("(a)(?i)\\1").unwrap();
+        assert!(re.partial_match("aA").is_some());
+
+        let e = Regex::new("(?ix)").unwrap_err();
+        assert_eq!(e.kind, parse::InvalidFlag);
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let mut flags = parse::Flags::new();
+        flags.ignore_case = true;
+
+        // Upper case by default, without mapping non-ASCII characters to ASCII
+        let re = Regex::with_flags("\u03c3", flags).unwrap();
+        assert!(re.partial_match("\u03a3").is_some());
+        let re = Regex::with_flags("[a-z]", flags).unwrap();
+        assert!(re.partial_match("\u017f").is_none());
+
+        flags.fold = case::SimpleFold;
+        let re = Regex::with_flags("\u03c3+", flags).unwrap();
+        let m = re.partial_match("x\u03a3\u03c3\u03c2").unwrap();
+        assert_eq!((m[0].start, m[0].end), (1, 4));
+        let re = Regex::with_flags("[a-z]", flags).unwrap();
+        assert!(re.partial_match("\u212a").is_some());
+        let re = Regex::with_flags("(\u00e9)\\1", flags).unwrap();
+        assert!(re.partial_match("\u00e9\u00c9").is_some());
+        let re = Regex::with_flags("\u00df", flags).unwrap();
+        assert!(re.partial_match("ss").is_none());
+
+        flags.fold = case::FullFold;
+        let re = Regex::with_flags("stra\u00dfe", flags).unwrap();
+        assert!(re.partial_match("STRASSE").is_some());
+        assert!(re.partial_match("Stra\u1e9ee").is_some());
+        let re = Regex::with_flags("strasse", flags).unwrap();
+        assert!(re.partial_match("Stra\u00dfe").is_some());
+        let re = Regex::with_flags("\\ufb01le", flags).unwrap();
+        assert!(re.partial_match("FILE").is_some());
+        let re = Regex::with_flags("file", flags).unwrap();
+        assert!(re.partial_match("\ufb01le").is_some());
+
+        // A quantified character is not combined into a sequence
+        let re = Regex::with_flags("^ss+$", flags).unwrap();
+        assert!(re.partial_match("\u00df").is_none());
+    }
+
+    #[test]
+    fn test_folded_class_escapes() {
+        let mut flags = parse::Flags::new();
+        flags.ignore_case = true;
+
+        // Folded the same on their own as inside a class
+        let inputs = ["a", "S", "k", "\u017f", "\u212a", "5", " ", "\u00a0", "_"];
+        for &level in [case::EcmaFold, case::SimpleFold].iter() {
+            flags.fold = level;
+            for &escape in ["\\d", "\\D", "\\s", "\\S", "\\w", "\\W"].iter() {
+                let re = Regex::with_flags(escape, flags).unwrap();
+                let class = Regex::with_flags("[".to_owned() + escape + "]", flags).unwrap();
+                for &input in inputs.iter() {
+                    assert_eq!(re.is_match(input), class.is_match(input));
+                }
+            }
+        }
+
+        // \u017f and \u212a fold to s and k, so \W matches those as well
+        flags.fold = case::SimpleFold;
+        let re = Regex::with_flags("^\\w+$", flags).unwrap();
+        assert!(re.is_match("\u017f\u212a"));
+        let re = Regex::with_flags("\\W", flags).unwrap();
+        assert!(re.is_match("s"));
+        let re = Regex::with_flags("\\d", flags).unwrap();
+        assert!(!re.is_match("a\u00a0"));
+        let re = Regex::with_flags("\\D", flags).unwrap();
+        assert!(!re.is_match("5"));
+        let re = Regex::with_flags("\\s", flags).unwrap();
+        assert!(!re.is_match("a5"));
+        let re = Regex::with_flags("\\S", flags).unwrap();
+        assert!(!re.is_match(" \u00a0"));
+    }
+
+    #[test]
+    fn test_multiline() {
+        let mut flags = parse::Flags::new();
+        flags.multiline = true;
+        let re = Regex::with_flags("^\\w+$", flags).unwrap();
+        let input = "first\r\nsecond";
+        let m = re.partial_match(input).unwrap();
+        assert_eq!(input.slice_chars(m[0].start, m[0].end), "first");
+
+        let re = Regex::new("^\\w+$").unwrap();
+        assert!(re.partial_match(input).is_none());
+    }
+
+    #[test]
+    fn test_dot_all() {
+        let re = Regex::new(".+").unwrap();
+        let m = re.partial_match("\r\nline\r\n").unwrap();
+        assert_eq!((m[0].start, m[0].end), (2, 6));
+
+        let mut flags = parse::Flags::new();
+        flags.dot_all = true;
+        let re = Regex::with_flags(".+", flags).unwrap();
+        let m = re.partial_match("\r\nline\r\n").unwrap();
+        assert_eq!((m[0].start, m[0].end), (0, 8));
+    }
+
+    #[test]
+    fn test_unicode_word() {
+        let mut flags = parse::Flags::new();
+        flags.unicode_word = true;
+
+        let re = Regex::with_flags("\\b\\w+\\b", flags).unwrap();
+        let input = "\u00fcber caf\u00e9";
+        let m = re.partial_match(input).unwrap();
+        assert_eq!(input.slice_chars(m[0].start, m[0].end), "\u00fcber");
+
+        let re = Regex::with_flags("caf\\B", flags).unwrap();
+        assert!(re.partial_match(input).is_some());
+
+        // Marks, decimal numbers and connector punctuation from any script
+        let re = Regex::with_flags("^\\w+$", flags).unwrap();
+        assert!(re.partial_match("e\u0301\u0663\u203f").is_some());
+        assert!(re.partial_match("a-b").is_none());
+
+        let re = Regex::with_flags("\\W", flags).unwrap();
+        assert!(re.partial_match("\u00e9\u03b1").is_none());
+    }
+
+    #[test]
+    fn test_character_class() {
+        // One instruction, with the ranges sorted and merged
+        let etree = parse::parse("[x0-9d-fa-c]", parse::Flags::new()).unwrap();
+        let code = compile::compile(&etree, 1000);
+        assert_eq!(code.len(), 2);
+        match code[0] {
+            compile::Class(ref class) => assert_eq!(class.ranges().to_owned(), ~[('0', '9'), ('a', 'f'), ('x', 'x')]),
+            _ => fail!("Expected a class")
+        }
+
+        let re = Regex::new("[^\\d\\s]+").unwrap();
+        assert_eq!(re.find("12 ab\u00e9\u2028").unwrap().as_str(), "ab\u00e9");
+        let re = Regex::new("\\p{L}+").unwrap();
+        assert_eq!(re.find("1\u03b1\u4e2d\U0001d400?").unwrap().as_str(), "\u03b1\u4e2d\U0001d400");
+        let re = Regex::new("[^\\p{L}]").unwrap();
+        assert!(!re.is_match("\u03b1\u4e2d"));
+        let re = Regex::new("[^\\s\\S]").unwrap();
+        assert!(!re.is_match("a \u00e9"));
+        let re = Regex::new("^[\\s\\S]$").unwrap();
+        assert!(re.is_match("\U0010ffff"));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let e = Regex::new("ab(cd").unwrap_err();
+        assert_eq!(e.kind, parse::UnterminatedGroup);
+        assert_eq!(e.span, (2, 5));
+
+        let e = Regex::new("a[z-a]").unwrap_err();
+        assert_eq!(e.kind, parse::InvalidRange);
+        assert_eq!(e.pos, 4);
+
+        let e = Regex::new("a{99999999999999999999,5}").unwrap_err();
+        assert_eq!(e.kind, parse::RepetitionTooLarge);
+        assert_eq!(e.span, (1, 23));
+
+        let e = Regex::new("abc)").unwrap_err();
+        assert_eq!(e.kind, parse::UnexpectedCloseParen);
+        assert_eq!(e.span, (3, 4));
+    }
 }
 
 #[cfg(test)]
@@ -156,7 +861,7 @@ mod bench {
     fn bench_compile_uri(b: &mut extra::test::BenchHarness) {
         let pattern = "([a-zA-Z][a-zA-Z0-9]*)://([^ /]+)(/[^ ]*)?";
         do b.iter {
-            Regex::new(pattern);
+            Regex::new(pattern).unwrap();
         }
     }
 
@@ -164,7 +869,7 @@ mod bench {
     fn bench_compile_email(b: &mut extra::test::BenchHarness) {
         let pattern = "([^ @]+)@([^ @]+)";
         do b.iter {
-            Regex::new(pattern);
+            Regex::new(pattern).unwrap();
         }
     }
 
@@ -172,7 +877,7 @@ mod bench {
     fn bench_compile_date(b: &mut extra::test::BenchHarness) {
         let pattern = "([0-9][0-9]?)/([0-9][0-9]?)/([0-9][0-9]([0-9][0-9])?)";
         do b.iter {
-            Regex::new(pattern);
+            Regex::new(pattern).unwrap();
         }
     }
 
@@ -180,7 +885,43 @@ mod bench {
     fn bench_compile_uri_or_email(b: &mut extra::test::BenchHarness) {
         let pattern = "([a-zA-Z][a-zA-Z0-9]*)://([^ /]+)(/[^ ]*)?|([^ @]+)@([^ @]+)";
         do b.iter {
-            Regex::new(pattern);
+            Regex::new(pattern).unwrap();
+        }
+    }
+
+    #[bench]
+    fn bench_compile_folded_property_class(b: &mut extra::test::BenchHarness) {
+        let pattern = "(?i)[\\p{L}\\p{N}_]+";
+        do b.iter {
+            Regex::new(pattern).unwrap();
+        }
+    }
+
+    static LOG_PATTERN: &'static str = "(\\d+)-(\\d+)-(\\d+) (\\w+): (ERROR|FATAL)";
+    static LOG_LINE: &'static str = "2013-10-07 server: WARN disk usage 91% on /var, 2013-10-07 server: ERROR out of space";
+
+    #[bench]
+    fn bench_partial_match_log(b: &mut extra::test::BenchHarness) {
+        let re = Regex::new(LOG_PATTERN).unwrap();
+        do b.iter {
+            re.partial_match(LOG_LINE);
+        }
+    }
+
+    #[bench]
+    fn bench_is_match_log(b: &mut extra::test::BenchHarness) {
+        let re = Regex::new(LOG_PATTERN).unwrap();
+        do b.iter {
+            re.is_match(LOG_LINE);
+        }
+    }
+
+    #[bench]
+    fn bench_is_match_unicode_class(b: &mut extra::test::BenchHarness) {
+        let re = Regex::new("\\p{L}+\\d").unwrap();
+        let input = "\u03b1\u03b2\u03b3 \u4e2d\u6587 caf\u00e9 ".repeat(20);
+        do b.iter {
+            re.is_match(input);
         }
     }
 
@@ -192,7 +933,7 @@ mod bench {
         //let input = "3.14";
         let pattern = "a(b|c)*d";
         let input = "abcd";
-        let re = Regex::new(pattern);
+        let re = Regex::new(pattern).unwrap();
 
         do b.iter {
             re.partial_match(input);
@@ -39,17 +39,108 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("^((?:19|20)\\d\\d)[- /.](0[1-9]|1[012])[- /.](0[1-9]|[12][0-9]|3[01])$", "1999-01-01", Match, "1999-01-01", &'static ["1999", "01", "01"]),
     ("^((?:19|20)\\d\\d)[- /.](0[1-9]|1[012])[- /.](0[1-9]|[12][0-9]|3[01])$", "1999/01-01", Match, "1999/01-01", &'static ["1999", "01", "01"]),
     ("^((?:19|20)\\d\\d)[- /.](0[1-9]|1[012])[- /.](0[1-9]|[12][0-9]|3[01])$", "1999-13-33", NoMatch, "", &'static []),
-    //("^((?:19|20)\\d\\d)([- /.])(0[1-9]|1[012])\2(0[1-9]|[12][0-9]|3[01])$", "199-01-01", Match, "1999-01-01", &'static ["1999", "-", "01", "01"]), NOT IMPLEMENTED
-    //("^((?:19|20)\\d\\d)([- /.])(0[1-9]|1[012])\2(0[1-9]|[12][0-9]|3[01])$", "199/01/01", Match, "1999/01/01", &'static ["1999", "/", "01", "01"]), NOT IMPLEMENTED
-    //("^((?:19|20)\\d\\d)([- /.])(0[1-9]|1[012])\2(0[1-9]|[12][0-9]|3[01])$", "199/01-01", NoMatch, "", &'static []), NOT IMPLEMENTED
+    ("^((?:19|20)\\d\\d)([- /.])(0[1-9]|1[012])\\2(0[1-9]|[12][0-9]|3[01])$", "1999-01-01", Match, "1999-01-01", &'static ["1999", "-", "01", "01"]),
+    ("^((?:19|20)\\d\\d)([- /.])(0[1-9]|1[012])\\2(0[1-9]|[12][0-9]|3[01])$", "1999/01/01", Match, "1999/01/01", &'static ["1999", "/", "01", "01"]),
+    ("^((?:19|20)\\d\\d)([- /.])(0[1-9]|1[012])\\2(0[1-9]|[12][0-9]|3[01])$", "1999/01-01", NoMatch, "", &'static []),
     // Mathing lines (not) containing certain words
-    //^(?=.*?\bmust-have\b)(?=.*?\bmandatory\b)((?!avoid|illegal).)*$.
+    ("^(?=.*?\\bmust-have\\b)(?=.*?\\bmandatory\\b)((?!avoid|illegal).)*$", "this must-have line is mandatory", Match, "this must-have line is mandatory", &'static ["y"]),
+    ("^(?=.*?\\bmust-have\\b)(?=.*?\\bmandatory\\b)((?!avoid|illegal).)*$", "this line is mandatory", NoMatch, "", &'static []),
+    ("^(?=.*?\\bmust-have\\b)(?=.*?\\bmandatory\\b)((?!avoid|illegal).)*$", "avoid this must-have mandatory line", NoMatch, "", &'static []),
     // Near operator emulation
     ("\\bword1\\W+(?:\\w+\\W+){1,6}?word2\\b", "word1 word2", NoMatch, "", &'static []),
     ("\\bword1\\W+(?:\\w+\\W+){1,6}?word2\\b", "word1 1 word2", Match, "word1 1 word2", &'static []),
     ("\\bword1\\W+(?:\\w+\\W+){1,6}?word2\\b", "word1 1 2 3 4 5 6 word2", Match, "word1 1 2 3 4 5 6 word2", &'static []),
     ("\\bword1\\W+(?:\\w+\\W+){1,6}?word2\\b", "word1 1 2 3 4 5 6 7 word", NoMatch, "", &'static []),
-    //("", "", Match, "", &'static []),
+    ("", "", Match, "", &'static []),
+
+    // Lookahead
+    ("foo(?=bar)", "foobar", Match, "foo", &'static []),
+    ("foo(?=bar)", "foobaz", NoMatch, "", &'static []),
+    ("foo(?!bar)", "foobar", NoMatch, "", &'static []),
+    ("foo(?!bar)", "foobaz", Match, "foo", &'static []),
+    ("^(?!.*secret).*$", "public data", Match, "public data", &'static []),
+    ("^(?!.*secret).*$", "top secret data", NoMatch, "", &'static []),
+    ("(?=(a+))a*b", "aaab", Match, "aaab", &'static ["aaa"]),
+    ("(?=a)*", "a", Match, "", &'static []),
+    ("(?=a)+a", "a", Match, "a", &'static []),
+    ("(?!a){2}b", "b", Match, "b", &'static []),
+    ("(?=a){1,2}b", "b", NoMatch, "", &'static []),
+    ("(?!a", "a", ParseError, "", &'static []),
+
+    // Lookbehind
+    ("(?<=key=)\\w+", "key=value", Match, "value", &'static []),
+    ("(?<=key=)\\w+", "kez=value", NoMatch, "", &'static []),
+    ("(?<!key=)\\b\\w+", "key=value", Match, "key", &'static []),
+    ("(?<=\\$)\\d+(\\.\\d*)?", "cost $10.53", Match, "10.53", &'static [".53"]),
+    ("(?<!\\$)\\b\\d+", "$10 20", Match, "20", &'static []),
+    ("(?<=a+b*)c", "aabbc", Match, "c", &'static []),
+    ("(?<=^|,)\\w+", "x,yz", Match, "x", &'static []),
+    ("(?<=(\\d+)(\\d+))$", "1053", Match, "", &'static ["1", "053"]),
+    ("(?<=(?=ab)a)b", "ab", Match, "b", &'static []),
+    ("(?<=a)*", "a", ParseError, "", &'static []),
+
+    // Loops over expressions that can match the empty string, where an
+    // optional iteration that matches nothing fails
+    ("(a*)*", "b", Match, "", &'static []),
+    ("(a*)*", "aab", Match, "aa", &'static ["aa"]),
+    ("(a*)+", "b", Match, "", &'static [""]),
+    ("(a|)*", "aab", Match, "aa", &'static ["a"]),
+    ("(|a)*", "aa", Match, "aa", &'static ["a"]),
+    ("(|a)+", "aa", Match, "aa", &'static ["a"]),
+    ("()*", "a", Match, "", &'static []),
+    ("(?:x?)+", "y", Match, "", &'static []),
+    ("(?:x?)+", "xxy", Match, "xx", &'static []),
+    ("(a?)+", "", Match, "", &'static [""]),
+    ("(a?){2,}", "a", Match, "a", &'static [""]),
+    ("(a?){2,3}", "a", Match, "a", &'static [""]),
+    ("(a*?)*x", "aax", Match, "aax", &'static ["a"]),
+    ("(?:a|b?)+", "ab", Match, "ab", &'static []),
+    ("(?:a{0,2}){3,}b", "aaaaab", Match, "aaaaab", &'static []),
+    ("(?:(?=a))*a", "a", Match, "a", &'static []),
+    ("(?:\\b)*a", "a", Match, "a", &'static []),
+    ("(?<=(?:a|)*)b", "ab", Match, "b", &'static []),
+    ("(a*)*\\1", "aab", Match, "aa", &'static ["a"]),
+    ("a+*", "a", ParseError, "", &'static []),
+    ("a{2}*", "aa", ParseError, "", &'static []),
+    ("a*{2}", "aa", ParseError, "", &'static []),
+    ("a???", "a", ParseError, "", &'static []),
+    ("\\b*", "a", ParseError, "", &'static []),
+    ("$+", "", ParseError, "", &'static []),
+    ("|a", "a", Match, "", &'static []),
+    ("a||b", "b", Match, "", &'static []),
+
+    // Counted repetitions, unrolled or counted in registers
+    ("a{3}", "aa", NoMatch, "", &'static []),
+    ("a{3}", "aaaa", Match, "aaa", &'static []),
+    ("\\d{1,3}", "12345", Match, "123", &'static []),
+    ("\\d{1,3}?", "12345", Match, "1", &'static []),
+    ("a{2,4}?b", "aaab", Match, "aaab", &'static []),
+    ("(?:\\d{1,3}\\.){3}\\d{1,3}", "ip 192.168.0.1", Match, "192.168.0.1", &'static []),
+    ("(?:\\d{1,3}\\.){3}\\d{1,3}", "192.168.0", NoMatch, "", &'static []),
+    ("(a|b){2,3}", "abab", Match, "aba", &'static ["a"]),
+    ("(a){0}", "a", Match, "", &'static []),
+    ("a{0,0}b", "ab", Match, "b", &'static []),
+    ("(?:a{2}){2,}", "aaaaa", Match, "aaaa", &'static []),
+    ("(?<=a{2})b", "aab", Match, "b", &'static []),
+    ("(?<=a{2})b", "ab", NoMatch, "", &'static []),
+    ("a{2,1}", "", ParseError, "", &'static []),
+    ("a{99999999999999999999}", "", ParseError, "", &'static []),
+    ("a{99999999999999999999,5}", "", ParseError, "", &'static []),
+    ("a{1,99999999999999999999}", "", ParseError, "", &'static []),
+    ("a{18446744073709551615,}", "aaa", NoMatch, "", &'static []),
+    ("a{0,18446744073709551615}", "aaa", Match, "aaa", &'static []),
+    ("a{1,2,3}", "", ParseError, "", &'static []),
+
+    // Control characters are ordinary input, also at the end
+    ("a.", "a\x03", Match, "a\x03", &'static []),
+    ("a.", "a", NoMatch, "", &'static []),
+    ("a[^b]", "a", NoMatch, "", &'static []),
+    ("a[\x00-\x1f]", "a", NoMatch, "", &'static []),
+    ("\\x03", "\x03", Match, "\x03", &'static []),
+    ("\\x03", "a", NoMatch, "", &'static []),
+    ("\\x03$", "a\x03", Match, "\x03", &'static []),
+    ("a\\b", "a\x03", Match, "a", &'static []),
+    ("(?s)a.*", "a\x03\x00\x03", Match, "a\x03\x00\x03", &'static []),
 
     // Unicode
     ("①②③", "①②③", Match, "①②③", &'static []),
@@ -63,26 +154,45 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     // (a{1, 2}?)
     // c{1,2}+e
 
-    /* NOT IMPLEMENTED
-    ('(?P<foo_123', '', ParseError),      # Unterminated group identifier
-    ('(?P<1>a)', '', ParseError),         # Begins with a digit
-    ('(?P<!>a)', '', ParseError),         # Begins with an illegal char
-    ('(?P<foo!>a)', '', ParseError),      # Begins with an illegal char
-
-    # Same tests, for the ?P= form
-    ('(?P<foo_123>a)(?P=foo_123', 'aa', ParseError),
-    ('(?P<foo_123>a)(?P=1)', 'aa', ParseError),
-    ('(?P<foo_123>a)(?P=!)', 'aa', ParseError),
-    ('(?P<foo_123>a)(?P=foo_124', 'aa', ParseError),  # Backref to undefined group
-
-    ('(?P<foo_123>a)', 'a', SUCCEED, 'g1', 'a'),
-    ('(?P<foo_123>a)(?P=foo_123)', 'aa', SUCCEED, 'g1', 'a'),
-    */
+    // Named groups
+    ("(?P<foo_123", "", ParseError, "", &'static []),      // Unterminated group identifier
+    ("(?P<1>a)", "", ParseError, "", &'static []),         // Begins with a digit
+    ("(?P<!>a)", "", ParseError, "", &'static []),         // Begins with an illegal char
+    ("(?P<foo!>a)", "", ParseError, "", &'static []),      // Begins with an illegal char
+
+    // Same tests, for the ?P= form
+    ("(?P<foo_123>a)(?P=foo_123", "aa", ParseError, "", &'static []),
+    ("(?P<foo_123>a)(?P=1)", "aa", ParseError, "", &'static []),
+    ("(?P<foo_123>a)(?P=!)", "aa", ParseError, "", &'static []),
+    ("(?P<foo_123>a)(?P=foo_124", "aa", ParseError, "", &'static []),  // Backref to undefined group
+
+    ("(?P<foo_123>a)", "a", Match, "a", &'static ["a"]),
+    ("(?P<foo_123>a)(?P=foo_123)", "aa", Match, "aa", &'static ["a"]),
+
+    // Same tests, for the ECMAScript form
+    ("(?<foo_123", "", ParseError, "", &'static []),
+    ("(?<1>a)", "", ParseError, "", &'static []),
+    ("(?<foo!>a)", "", ParseError, "", &'static []),
+    ("(?<foo_123>a)\\k<foo_124>", "aa", ParseError, "", &'static []),
+    ("(?<foo>a)(?<foo>b)", "ab", ParseError, "", &'static []),
+    ("(?<foo_123>a)", "a", Match, "a", &'static ["a"]),
+    ("(?<foo_123>a)\\k<foo_123>", "aa", Match, "aa", &'static ["a"]),
+    ("(?<$x>a)(?<y>b)\\k<$x>", "aba", Match, "aba", &'static ["a", "b"]),
+    // References to groups defined later match the empty string
+    ("\\k<x>(?<x>a)", "a", Match, "a", &'static ["a"]),
+    ("(?P=x)(?P<x>a)", "a", Match, "a", &'static ["a"]),
+    ("(?i)\\k<x>b(?<x>a)", "BA", Match, "BA", &'static ["A"]),
+    ("\\k<y>(?<x>a)", "a", ParseError, "", &'static []),
 
     // Test octal escapes
-    //("\\1", 'a', ParseError),    // Backreference FAILS
+    ("\\1", "a", ParseError, "", &'static []),
+    ("(a)\\2", "aa", ParseError, "", &'static []),
+    ("\\1(a)", "a", Match, "a", &'static ["a"]),
+    ("(a)|\\1b", "b", Match, "b", &'static []),
+    ("(?<=\\1(a))b", "aab", Match, "b", &'static ["a"]),
+    ("(?<=\\1(a))b", "bab", NoMatch, "", &'static []),
     //("[\\1]", "\\1", Match, "\\1", &'static []), FAILS
-    //("\\09", "\x09", Match, "", &'static []), FAILS
+    ("\\09", "\x009", ParseError, "", &'static []),
 
     // From http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.11
     // If \ is followed by a decimal number n whose first digit is not 0, 
@@ -91,9 +201,83 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     // in the entire regular expression. \0 represents the <NUL> character and 
     // cannot be followed by a decimal digit.
     //('\\141', 'a', SUCCEED, 'found', 'a'),
-    //("\\0141", "a", Match, "", &'static []), FAILS
+    ("\\0141", "a", ParseError, "", &'static []),
     //('(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)(l)\\119', 'abcdefghijklk9', SUCCEED, 'found+"-"+g11', 'abcdefghijklk9-k'),
-    //("(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)(l)\\0119", "abcdefghijklk9", Match, "", &'static []), FAILS
+    ("(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)(k)(l)\\0119", "abcdefghijklk9", ParseError, "", &'static []),
+
+    // Character escapes
+    // http://www.ecma-international.org/ecma-262/5.1/#sec-15.10.2.10
+    ("\\0", "\x00", Match, "\x00", &'static []),
+    ("[\\0a]", "\x00", Match, "\x00", &'static []),
+    ("[a\\0]", "\x00", Match, "\x00", &'static []),
+    ("[^a\\0]", "\x00", NoMatch, "", &'static []),
+    ("\\f\\n\\r\\t\\v", "\x0c\n\r\t\x0b", Match, "\x0c\n\r\t\x0b", &'static []),
+    ("[\\b][\\f][\\n][\\r][\\t][\\v]", "\x08\x0c\n\r\t\x0b", Match, "\x08\x0c\n\r\t\x0b", &'static []),
+    ("\\n", "n", NoMatch, "", &'static []),
+    ("\\x41", "A", Match, "A", &'static []),
+    ("\\x41", "x41", NoMatch, "", &'static []),
+    ("\\xff", "\u00ff", Match, "\u00ff", &'static []),
+    ("\\x00f", "\x00f", Match, "\x00f", &'static []),
+    ("\\x4", "", ParseError, "", &'static []),
+    ("\\xg0", "", ParseError, "", &'static []),
+    ("[\\x41-\\x43]+", "xABCD", Match, "ABC", &'static []),
+    ("[\\x43-\\x41]", "", ParseError, "", &'static []),
+    ("\\u00e9", "caf\u00e9", Match, "\u00e9", &'static []),
+    ("\\u03A3", "\u03a3", Match, "\u03a3", &'static []),
+    ("\\u00e", "", ParseError, "", &'static []),
+    ("\\ud83d\\ude00", "\U0001f600", Match, "\U0001f600", &'static []),
+    ("[\\ud83d\\ude00]", "\U0001f600", Match, "\U0001f600", &'static []),
+    ("\\ud83d", "", ParseError, "", &'static []),
+    ("\\ude00", "", ParseError, "", &'static []),
+    ("\\ud83d\\u0041", "", ParseError, "", &'static []),
+    ("\\u{1F600}", "\U0001f600", Match, "\U0001f600", &'static []),
+    ("\\u{41}\\u{000042}", "AB", Match, "AB", &'static []),
+    ("[\\u{61}-\\u{63}]+", "abcd", Match, "abc", &'static []),
+    ("\\u{}", "", ParseError, "", &'static []),
+    ("\\u{110000}", "", ParseError, "", &'static []),
+    ("\\u{D800}", "", ParseError, "", &'static []),
+    ("\\u{41", "", ParseError, "", &'static []),
+    ("\\cJ", "\n", Match, "\n", &'static []),
+    ("\\cj", "\n", Match, "\n", &'static []),
+    ("[\\cA-\\cZ]+", "a\x01\x1a", Match, "\x01\x1a", &'static []),
+    ("\\c1", "", ParseError, "", &'static []),
+    ("\\c", "", ParseError, "", &'static []),
+    ("(?i)\\x61", "A", Match, "A", &'static []),
+    ("\\q\\-\\.", "q-.", Match, "q-.", &'static []),
+
+    // Unicode property escapes
+    // http://www.unicode.org/reports/tr18/#RL1.2
+    ("\\p{L}+", "123\u00e9t\u00e9!", Match, "\u00e9t\u00e9", &'static []),
+    ("\\p{Letter}+", "123abc", Match, "abc", &'static []),
+    ("\\p{Lu}", "abc", NoMatch, "", &'static []),
+    ("\\p{Lu}\\p{Ll}+", "x\u03a3\u03c3\u03c2", Match, "\u03a3\u03c3\u03c2", &'static []),
+    ("\\p{Nd}+", "x\u0661\u0662\u0663x", Match, "\u0661\u0662\u0663", &'static []),
+    ("\\p{gc=Nd}+", "x42", Match, "42", &'static []),
+    ("\\p{General_Category=Decimal_Number}+", "x42", Match, "42", &'static []),
+    ("\\P{L}+", "abc, 123def", Match, ", 123", &'static []),
+    ("\\p{Script=Greek}+", "abc\u03b1\u03b2\u03b3", Match, "\u03b1\u03b2\u03b3", &'static []),
+    ("\\p{sc=Grek}", "abc", NoMatch, "", &'static []),
+    ("\\P{Script=Latin}+", "abc\u0436\u0437def", Match, "\u0436\u0437", &'static []),
+    ("\\p{Alphabetic}+", "1\u00c5\u0905x2", Match, "\u00c5\u0905x", &'static []),
+    ("\\p{Alpha}", "1", NoMatch, "", &'static []),
+    ("\\p{White_Space}+", "a \u3000\tb", Match, " \u3000\t", &'static []),
+    ("\\p{Any}", "\U0010ffff", Match, "\U0010ffff", &'static []),
+    ("\\P{Any}", "abc", NoMatch, "", &'static []),
+    ("\\p{Cs}", "abc", NoMatch, "", &'static []),
+    ("\\P{Co}", "\ue000", NoMatch, "", &'static []),
+    ("\\P{Co}", "\ud7ff", Match, "\ud7ff", &'static []),
+    ("[\\p{Lu}\\d]+", "ab12CDe", Match, "12CD", &'static []),
+    ("[^\\p{L}]+", "ab12, CDe", Match, "12, ", &'static []),
+    ("[\\P{L}a]+", "bx12a,c", Match, "12a,", &'static []),
+    ("(?i)\\p{Lu}+", "abC", Match, "abC", &'static []),
+    ("(?i)[\\p{Lu}]+", "abC", Match, "abC", &'static []),
+    ("\\p{Foo}", "", ParseError, "", &'static []),
+    ("\\p{Script=Foo}", "", ParseError, "", &'static []),
+    ("\\p{Foo=Greek}", "", ParseError, "", &'static []),
+    ("\\p{greek}", "", ParseError, "", &'static []),
+    ("\\pL", "", ParseError, "", &'static []),
+    ("\\p{L", "", ParseError, "", &'static []),
+    ("[\\p]", "", ParseError, "", &'static []),
     /*
     # Test \0 is handled everywhere
     (r'\0', '\0', SUCCEED, 'found', '\0'),
@@ -133,7 +317,7 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
 
     // Python tests suite
     (")", "", ParseError, "", &'static []),
-    //("", "", Match, "", &'static []), FAILS no VoidExpression (yet?)
+    ("", "", Match, "", &'static []),
     ("abc", "abc", Match, "abc", &'static []),
     ("abc", "xbc", NoMatch, "", &'static []),
     ("abc", "axc", NoMatch, "", &'static []),
@@ -204,9 +388,12 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("\\By\\b", "xy", Match, "y", &'static []),
     ("\\by\\B", "yz", Match, "y", &'static []),
     ("\\By\\B", "xyz", Match, "y", &'static []),
+    ("\\bcaf\\b", "caf\u00e9", Match, "caf", &'static []),
+    ("\\b\\w+\\b", "\u00fcber", Match, "ber", &'static []),
+    ("\\B\\w+", "\u00e9t\u00e9", NoMatch, "", &'static []),
     ("ab|cd", "abc", Match, "ab", &'static []),
     ("ab|cd", "abcd", Match, "ab", &'static []),
-    //("()ef", "def", Match, "ef", &'static [""]), FAILS there's no VoidExpression (yet?)
+    ("()ef", "def", Match, "ef", &'static [""]),
     ("$b", "b", NoMatch, "", &'static []),
     ("a\\(b", "a(b", Match, "a(b", &'static []),
     ("a\\(*b", "ab", Match, "ab", &'static []),
@@ -230,7 +417,7 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("(ab|cd)e", "abcde", Match, "cde", &'static ["cd"]),
     ("[abhgefdc]ij", "hij", Match, "hij", &'static []),
     ("^(ab|cd)e", "abcde", NoMatch, "", &'static []),
-    //("(abc|)ef", "abcdef", Match, "ef", &'static [""]), FAILS no VoidExpression (yet?)
+    ("(abc|)ef", "abcdef", Match, "ef", &'static [""]),
     ("(a|b)c*d", "abcd", Match, "bcd", &'static ["b"]),
     ("(ab|ab*)bc", "abc", Match, "abc", &'static ["a"]),
     ("a([bc]*)c*", "abc", Match, "abc", &'static ["bc"]),
@@ -255,22 +442,22 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("\\((.*), (.*)\\)", "(a, b)", Match, "(a, b)", &'static ["a", "b"]),
     ("[k]", "ab", NoMatch, "", &'static []),
     ("a[-]?c", "ac", Match, "ac", &'static []),
-    //("(abc)\\1", "abcabc", Match, "g1", "abc"), NOT IMPLEMNTED
-    //("([a-c]*)\\1", "abcabc", Match, "g1", "abc"), NOT IMPLEMNTED
+    ("(abc)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
+    ("([a-c]*)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
     ("^(.+)?B", "AB", Match, "AB", &'static ["A"]),
-    //("(a+).\\1$", "aaaaa", Match, "aaaaa", &'static ["aa"]), NOT IMPLEMENTED
-    //("^(a+).\\1$", "aaaa", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(abc)\\1", "abcabc", Match, "abcabc", &'static ["abc"]), NOT IMPLEMENTED
-    //("([a-c]+)\\1", "abcabc", Match, "abcabc", &'static ["abc"]), NOT IMPLEMENTED
-    //("(a)\\1", "aa", Match, ""aa, &'static ["a"]), NOT IMPLEMENTED
-    //("(a+)\\1", "aa", Match, "aa", &'static ["a"]), NOT IMPLEMENTED
-    //("(a+)+\\1", "aa", Match, "aa", &'static ["a"]), NOT IMPLEMENTED
-    //("(a).+\\1", "aba", Match, "aba", &'static ["a"]), NOT IMPLEMENTED
-    //("(a)ba*\\1", "aba", Match, "aba", &'static ["a"]), NOT IMPLEMENTED
-    //("(aa|a)a\\1$", "aaa", Match, "aaa", &'static ["a"]), NOT IMPLEMENTED
-    //("(a|aa)a\\1$", "aaa", Match, "aaa", &'static ["a"]), NOT IMPLEMENTED
-    //("(a+)a\\1$", "aaa", Match, "aaa", &'static ["a"]), NOT IMPLEMENTED
-    //("([abc]*)\\1", "abcabc", Match, "abcabc", &'static ["abc"]), NOT IMPLEMENTED
+    ("(a+).\\1$", "aaaaa", Match, "aaaaa", &'static ["aa"]),
+    ("^(a+).\\1$", "aaaa", NoMatch, "", &'static []),
+    ("(abc)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
+    ("([a-c]+)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
+    ("(a)\\1", "aa", Match, "aa", &'static ["a"]),
+    ("(a+)\\1", "aa", Match, "aa", &'static ["a"]),
+    ("(a+)+\\1", "aa", Match, "aa", &'static ["a"]),
+    ("(a).+\\1", "aba", Match, "aba", &'static ["a"]),
+    ("(a)ba*\\1", "aba", Match, "aba", &'static ["a"]),
+    ("(aa|a)a\\1$", "aaa", Match, "aaa", &'static ["a"]),
+    ("(a|aa)a\\1$", "aaa", Match, "aaa", &'static ["a"]),
+    ("(a+)a\\1$", "aaa", Match, "aaa", &'static ["a"]),
+    ("([abc]*)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
     //("(a)(b)c|ab", "ab", Match, "ab", &'static ["", ""]), FAILS doesn't capture on paths not taken
     ("(a)+x", "aaax", Match, "aaax", &'static ["a"]),
     ("([ac])+x", "aacx", Match, "aacx", &'static ["c"]),
@@ -283,14 +470,13 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("([xyz]*)x", "abcx", Match, "x", &'static [""]),
     //("(a)+b|aac", "aac", Match, "aac", &'static [""]), FAILS doesn't capture on paths not taken
 
-    /* NOT IMPLEMENTED
-    # Test symbolic groups
-
-    ('(?P<i d>aaa)a', 'aaaa', ParseError),
-    ('(?P<id>aaa)a', 'aaaa', SUCCEED, 'found+"-"+id', 'aaaa-aaa'),
-    ('(?P<id>aa)(?P=id)', 'aaaa', SUCCEED, 'found+"-"+id', 'aaaa-aa'),
-    ('(?P<id>aa)(?P=xd)', 'aaaa', ParseError),
+    // Test symbolic groups
+    ("(?P<i d>aaa)a", "aaaa", ParseError, "", &'static []),
+    ("(?P<id>aaa)a", "aaaa", Match, "aaaa", &'static ["aaa"]),
+    ("(?P<id>aa)(?P=id)", "aaaa", Match, "aaaa", &'static ["aa"]),
+    ("(?P<id>aa)(?P=xd)", "aaaa", ParseError, "", &'static []),
 
+    /* NOT IMPLEMENTED
     # Test octal escapes/memory references
 
     ('\\1', 'a', ParseError),
@@ -357,7 +543,7 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     //("a[^]b]c", "adc", Match, "", &'static []), FAILS
     ("ab|cd", "abc", Match, "ab", &'static []),
     ("ab|cd", "abcd", Match, "ab", &'static []),
-    //("()ef", "def", Match, "", &'static []), FAILS no VoidExpression (yet?)
+    ("()ef", "def", Match, "ef", &'static [""]),
     ("*a", "-", ParseError, "", &'static []),
     ("(*)b", "-", ParseError, "", &'static []),
     ("$b", "b", NoMatch, "", &'static []),
@@ -372,7 +558,7 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("(a)b(c)", "abc", Match, "abc", &'static ["a", "c"]),
     ("a+b+c", "aabbabc", Match, "abc", &'static []),
     ("a{1,}b{1,}c", "aabbabc", Match, "abc", &'static []),
-    ("a**", "-", ParseError, "", &'static []), // Would cause an infinite loop if accepted
+    ("a**", "-", ParseError, "", &'static []), // A quantifier can't be quantified
     ("a.+?c", "abcabc", Match, "abc", &'static []),
     ("(a+|b)*", "ab", Match, "ab", &'static ["b"]),
     ("(a+|b){0,}", "ab", Match, "ab", &'static ["b"]),
@@ -394,7 +580,7 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("(ab|cd)e", "abcde", Match, "cde", &'static ["cd"]),
     ("[abhgefdc]ij", "hij", Match, "hij", &'static []),
     ("^(ab|cd)e", "abcde", NoMatch, "", &'static []),
-    //("(abc|)ef", "abcdef", Match, "", &'static []), FAILS no VoidExpression (yet?)
+    ("(abc|)ef", "abcdef", Match, "ef", &'static [""]),
     ("(a|b)c*d", "abcd", Match, "bcd", &'static ["b"]),
     ("(ab|ab*)bc", "abc", Match, "abc", &'static ["a"]),
     ("a([bc]*)c*", "abc", Match, "abc", &'static ["bc"]),
@@ -412,12 +598,12 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("(bc+d$|ef*g.|h?i(j|k))", "effg", NoMatch, "", &'static []),
     ("(bc+d$|ef*g.|h?i(j|k))", "bcdd", NoMatch, "", &'static []),
     //("(bc+d$|ef*g.|h?i(j|k))", "reffgz", Match, "effgz", &'static ["effgz", ""]), FAILS doesn't capture on paths not taken
-    //("((((((((((a))))))))))", "a", Match, "a", &'static ["a", "a", "a", "a", "a", "a", "a", "a", "a", "a"]), FAILS only support for 9 captures
-    //("((((((((((a))))))))))\\10", "aa", Match, "", &'static []), NOT IMPLEMENTED
+    ("((((((((((a))))))))))", "a", Match, "a", &'static ["a", "a", "a", "a", "a", "a", "a", "a", "a", "a"]),
+    ("((((((((((a))))))))))\\10", "aa", Match, "aa", &'static ["a", "a", "a", "a", "a", "a", "a", "a", "a", "a"]),
 // Python does not have the same rules for \\41 so this is a syntax error
 //      ("((((((((((a))))))))))\\41", "aa", NoMatch, "", &'static []),
 //      ("((((((((((a))))))))))\\41", "a!", Match, "", &'static []),
-    //("((((((((((a))))))))))\\41", "", ParseError, "", &'static []), FAILS
+    ("((((((((((a))))))))))\\41", "", ParseError, "", &'static []),
     //("(?i)((((((((((a))))))))))\\41", "", ParseError), NOT IMPLEMENTED
     ("(((((((((a)))))))))", "a", Match, "a", &'static ["a", "a", "a", "a", "a", "a", "a", "a", "a"]),
     ("multiple words of text", "uh-uh", NoMatch, "", &'static []),
@@ -426,155 +612,164 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     ("\\((.*), (.*)\\)", "(a, b)", Match, "(a, b)", &'static ["a", "b"]),
     ("[k]", "ab", NoMatch, "", &'static []),
     ("a[-]?c", "ac", Match, "ac", &'static []),
-    //("(abc)\\1", "abcabc", Match, "g1", "abc"), NOT IMPLEMENTED
-    //("([a-c]*)\\1", "abcabc", Match, "g1", "abc"), NOT IMPLEMENTED
-    //("(?i)abc", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc", "XBC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc", "AXC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc", "ABX", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc", "XABCY", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc", "ABABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab*c", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab*bc", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab*bc", "ABBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab*?bc", "ABBBBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{0,}?bc", "ABBBBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab+?bc", "ABBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab+bc", "ABC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab+bc", "ABQ", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{1,}bc", "ABQ", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab+bc", "ABBBBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{1,}?bc", "ABBBBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{1,3}?bc", "ABBBBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{3,4}?bc", "ABBBBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{4,5}?bc", "ABBBBC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab??bc", "ABBC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab??bc", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{0,1}?bc", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab??bc", "ABBBBC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab??c", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab{0,1}?c", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^abc$", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^abc$", "ABCC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^abc", "ABCC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^abc$", "AABC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc$", "AABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)$", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a.c", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a.c", "AXC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a.*?c", "AXYZC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a.*c", "AXYZD", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[bc]d", "ABC", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[bc]d", "ABD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[b-d]e", "ABD", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[b-d]e", "ACE", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[b-d]", "AAC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[-b]", "A-", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[b-]", "A-", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[b-a]", "-", ParseError),
-    //("(?i)a[]b", "-", ParseError),
-    //("(?i)a[", "-", ParseError),
-    //("(?i)a]", "A]", Match, "", &'static []), NOT IMPLEMENTED
+    ("(abc)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
+    ("([a-c]*)\\1", "abcabc", Match, "abcabc", &'static ["abc"]),
+    ("(?i)abc", "ABC", Match, "ABC", &'static []),
+    ("(?i)abc", "XBC", NoMatch, "", &'static []),
+    ("(?i)abc", "AXC", NoMatch, "", &'static []),
+    ("(?i)abc", "ABX", NoMatch, "", &'static []),
+    ("(?i)abc", "XABCY", Match, "ABC", &'static []),
+    ("(?i)abc", "ABABC", Match, "ABC", &'static []),
+    ("(?i)ab*c", "ABC", Match, "ABC", &'static []),
+    ("(?i)ab*bc", "ABC", Match, "ABC", &'static []),
+    ("(?i)ab*bc", "ABBC", Match, "ABBC", &'static []),
+    ("(?i)ab*?bc", "ABBBBC", Match, "ABBBBC", &'static []),
+    ("(?i)ab{0,}?bc", "ABBBBC", Match, "ABBBBC", &'static []),
+    ("(?i)ab+?bc", "ABBC", Match, "ABBC", &'static []),
+    ("(?i)ab+bc", "ABC", NoMatch, "", &'static []),
+    ("(?i)ab+bc", "ABQ", NoMatch, "", &'static []),
+    ("(?i)ab{1,}bc", "ABQ", NoMatch, "", &'static []),
+    ("(?i)ab+bc", "ABBBBC", Match, "ABBBBC", &'static []),
+    ("(?i)ab{1,}?bc", "ABBBBC", Match, "ABBBBC", &'static []),
+    ("(?i)ab{1,3}?bc", "ABBBBC", Match, "ABBBBC", &'static []),
+    ("(?i)ab{3,4}?bc", "ABBBBC", Match, "ABBBBC", &'static []),
+    ("(?i)ab{4,5}?bc", "ABBBBC", NoMatch, "", &'static []),
+    ("(?i)ab??bc", "ABBC", Match, "ABBC", &'static []),
+    ("(?i)ab??bc", "ABC", Match, "ABC", &'static []),
+    ("(?i)ab{0,1}?bc", "ABC", Match, "ABC", &'static []),
+    ("(?i)ab??bc", "ABBBBC", NoMatch, "", &'static []),
+    ("(?i)ab??c", "ABC", Match, "ABC", &'static []),
+    ("(?i)ab{0,1}?c", "ABC", Match, "ABC", &'static []),
+    ("(?i)^abc$", "ABC", Match, "ABC", &'static []),
+    ("(?i)^abc$", "ABCC", NoMatch, "", &'static []),
+    ("(?i)^abc", "ABCC", Match, "ABC", &'static []),
+    ("(?i)^abc$", "AABC", NoMatch, "", &'static []),
+    ("(?i)abc$", "AABC", Match, "ABC", &'static []),
+    ("(?i)^", "ABC", Match, "", &'static []),
+    ("(?i)$", "ABC", Match, "", &'static []),
+    ("(?i)a.c", "ABC", Match, "ABC", &'static []),
+    ("(?i)a.c", "AXC", Match, "AXC", &'static []),
+    ("(?i)a.*?c", "AXYZC", Match, "AXYZC", &'static []),
+    ("(?i)a.*c", "AXYZD", NoMatch, "", &'static []),
+    ("(?i)a[bc]d", "ABC", NoMatch, "", &'static []),
+    ("(?i)a[bc]d", "ABD", Match, "ABD", &'static []),
+    ("(?i)a[b-d]e", "ABD", NoMatch, "", &'static []),
+    ("(?i)a[b-d]e", "ACE", Match, "ACE", &'static []),
+    ("(?i)a[b-d]", "AAC", Match, "AC", &'static []),
+    ("(?i)a[-b]", "A-", Match, "A-", &'static []),
+    ("(?i)a[b-]", "A-", Match, "A-", &'static []),
+    ("(?i)a[b-a]", "-", ParseError, "", &'static []),
+    ("(?i)a[]b", "-", ParseError, "", &'static []),
+    ("(?i)a[", "-", ParseError, "", &'static []),
+    ("(?i)a]", "A]", Match, "A]", &'static []),
     //("(?i)a[]]b", "A]B", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[^bc]d", "AED", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[^bc]d", "ABD", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[^-b]c", "ADC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[^-b]c", "A-C", NoMatch, "", &'static []), NOT IMPLEMENTED
+    ("(?i)a[^bc]d", "AED", Match, "AED", &'static []),
+    ("(?i)a[^bc]d", "ABD", NoMatch, "", &'static []),
+    ("(?i)a[^-b]c", "ADC", Match, "ADC", &'static []),
+    ("(?i)a[^-b]c", "A-C", NoMatch, "", &'static []),
     //("(?i)a[^]b]c", "A]C", NoMatch, "", &'static []), NOT IMPLEMENTED
     //("(?i)a[^]b]c", "ADC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab|cd", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab|cd", "ABCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)()ef", "DEF", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)*a", "-", ParseError),
-    //("(?i)(*)b", "-", ParseError),
-    //("(?i)$b", "B", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a\\", "-", ParseError),
-    //("(?i)a\\(b", "A(B", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a\\(*b", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a\\(*b", "A((B", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a\\\\b", "A\\B", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc)", "-", ParseError),
-    //("(?i)(abc", "-", ParseError),
-    //("(?i)((a))", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a)b(c)", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a+b+c", "AABBABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a{1,}b{1,}c", "AABBABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a**", "-", ParseError),
-    //("(?i)a.+?c", "ABCABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a.*?c", "ABCABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a.{0,5}?c", "ABCABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b)*", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b){0,}", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b)+", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b){1,}", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b)?", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b){0,1}", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a+|b){0,1}?", "AB", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i))(", "-", ParseError),
-    //("(?i)[^ab]*", "CDE", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abc", "", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a*", "", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)([abc])*d", "ABBBCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)([abc])*bcd", "ABCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a|b|c|d|e", "E", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a|b|c|d|e)f", "EF", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)abcd*efg", "ABCDEFG", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab*", "XABYABBBZ", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)ab*", "XAYABBBZ", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(ab|cd)e", "ABCDE", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)[abhgefdc]ij", "HIJ", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^(ab|cd)e", "ABCDE", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(abc|)ef", "ABCDEF", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(a|b)c*d", "ABCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(ab|ab*)bc", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a([bc]*)c*", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a([bc]*)(c*d)", "ABCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a([bc]+)(c*d)", "ABCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a([bc]*)(c+d)", "ABCD", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[bcd]*dcdcde", "ADCDCDE", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)a[bcd]+dcdcde", "ADCDCDE", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(ab|a)b*c", "ABC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)((a)(b)c)(d)", "ABCD", Match, "g1+"-"+g2+"-"+g3+"-"+g4", "ABC-A-B-D"), NOT IMPLEMENTED
-    //("(?i)[a-zA-Z_][a-zA-Z0-9_]*", "ALPHA", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)^a(bc+|b[eh])g|.h$", "ABH", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(bc+d$|ef*g.|h?i(j|k))", "EFFGZ", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(bc+d$|ef*g.|h?i(j|k))", "IJ", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(bc+d$|ef*g.|h?i(j|k))", "EFFG", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(bc+d$|ef*g.|h?i(j|k))", "BCDD", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(bc+d$|ef*g.|h?i(j|k))", "REFFGZ", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)((((((((((a))))))))))", "A", Match, "g10", "A"), NOT IMPLEMENTED
-    //("(?i)((((((((((a))))))))))\\10", "AA", Match, "", &'static []), NOT IMPLEMENTED
+    ("(?i)ab|cd", "ABC", Match, "AB", &'static []),
+    ("(?i)ab|cd", "ABCD", Match, "AB", &'static []),
+    ("(?i)()ef", "DEF", Match, "EF", &'static [""]),
+    ("(?i)*a", "-", ParseError, "", &'static []),
+    ("(?i)(*)b", "-", ParseError, "", &'static []),
+    ("(?i)$b", "B", NoMatch, "", &'static []),
+    ("(?i)a\\", "-", ParseError, "", &'static []),
+    ("(?i)a\\(b", "A(B", Match, "A(B", &'static []),
+    ("(?i)a\\(*b", "AB", Match, "AB", &'static []),
+    ("(?i)a\\(*b", "A((B", Match, "A((B", &'static []),
+    ("(?i)a\\\\b", "A\\B", Match, "A\\B", &'static []),
+    ("(?i)abc)", "-", ParseError, "", &'static []),
+    ("(?i)(abc", "-", ParseError, "", &'static []),
+    ("(?i)((a))", "ABC", Match, "A", &'static ["A", "A"]),
+    ("(?i)(a)b(c)", "ABC", Match, "ABC", &'static ["A", "C"]),
+    ("(?i)a+b+c", "AABBABC", Match, "ABC", &'static []),
+    ("(?i)a{1,}b{1,}c", "AABBABC", Match, "ABC", &'static []),
+    ("(?i)a**", "-", ParseError, "", &'static []),
+    ("(?i)a.+?c", "ABCABC", Match, "ABC", &'static []),
+    ("(?i)a.*?c", "ABCABC", Match, "ABC", &'static []),
+    ("(?i)a.{0,5}?c", "ABCABC", Match, "ABC", &'static []),
+    ("(?i)(a+|b)*", "AB", Match, "AB", &'static ["B"]),
+    ("(?i)(a+|b){0,}", "AB", Match, "AB", &'static ["B"]),
+    ("(?i)(a+|b)+", "AB", Match, "AB", &'static ["B"]),
+    ("(?i)(a+|b){1,}", "AB", Match, "AB", &'static ["B"]),
+    ("(?i)(a+|b)?", "AB", Match, "A", &'static ["A"]),
+    ("(?i)(a+|b){0,1}", "AB", Match, "A", &'static ["A"]),
+    ("(?i)(a+|b){0,1}?", "AB", Match, "", &'static []),
+    ("(?i))(", "-", ParseError, "", &'static []),
+    ("(?i)[^ab]*", "CDE", Match, "CDE", &'static []),
+    ("(?i)abc", "", NoMatch, "", &'static []),
+    ("(?i)a*", "", Match, "", &'static []),
+    ("(?i)([abc])*d", "ABBBCD", Match, "ABBBCD", &'static ["C"]),
+    ("(?i)([abc])*bcd", "ABCD", Match, "ABCD", &'static ["A"]),
+    ("(?i)a|b|c|d|e", "E", Match, "E", &'static []),
+    ("(?i)(a|b|c|d|e)f", "EF", Match, "EF", &'static ["E"]),
+    ("(?i)abcd*efg", "ABCDEFG", Match, "ABCDEFG", &'static []),
+    ("(?i)ab*", "XABYABBBZ", Match, "AB", &'static []),
+    ("(?i)ab*", "XAYABBBZ", Match, "A", &'static []),
+    ("(?i)(ab|cd)e", "ABCDE", Match, "CDE", &'static ["CD"]),
+    ("(?i)[abhgefdc]ij", "HIJ", Match, "HIJ", &'static []),
+    ("(?i)^(ab|cd)e", "ABCDE", NoMatch, "", &'static []),
+    ("(?i)(abc|)ef", "ABCDEF", Match, "EF", &'static [""]),
+    ("(?i)(a|b)c*d", "ABCD", Match, "BCD", &'static ["B"]),
+    ("(?i)(ab|ab*)bc", "ABC", Match, "ABC", &'static ["A"]),
+    ("(?i)a([bc]*)c*", "ABC", Match, "ABC", &'static ["BC"]),
+    ("(?i)a([bc]*)(c*d)", "ABCD", Match, "ABCD", &'static ["BC", "D"]),
+    ("(?i)a([bc]+)(c*d)", "ABCD", Match, "ABCD", &'static ["BC", "D"]),
+    ("(?i)a([bc]*)(c+d)", "ABCD", Match, "ABCD", &'static ["B", "CD"]),
+    ("(?i)a[bcd]*dcdcde", "ADCDCDE", Match, "ADCDCDE", &'static []),
+    ("(?i)a[bcd]+dcdcde", "ADCDCDE", NoMatch, "", &'static []),
+    ("(?i)(ab|a)b*c", "ABC", Match, "ABC", &'static ["AB"]),
+    ("(?i)((a)(b)c)(d)", "ABCD", Match, "ABCD", &'static ["ABC", "A", "B", "D"]),
+    ("(?i)[a-zA-Z_][a-zA-Z0-9_]*", "ALPHA", Match, "ALPHA", &'static []),
+    ("(?i)^a(bc+|b[eh])g|.h$", "ABH", Match, "BH", &'static []),
+    ("(?i)(bc+d$|ef*g.|h?i(j|k))", "EFFGZ", Match, "EFFGZ", &'static ["EFFGZ"]),
+    ("(?i)(bc+d$|ef*g.|h?i(j|k))", "IJ", Match, "IJ", &'static ["IJ", "J"]),
+    ("(?i)(bc+d$|ef*g.|h?i(j|k))", "EFFG", NoMatch, "", &'static []),
+    ("(?i)(bc+d$|ef*g.|h?i(j|k))", "BCDD", NoMatch, "", &'static []),
+    ("(?i)(bc+d$|ef*g.|h?i(j|k))", "REFFGZ", Match, "EFFGZ", &'static ["EFFGZ"]),
+    ("(?i)((((((((((a))))))))))", "A", Match, "A", &'static ["A", "A", "A", "A", "A", "A", "A", "A", "A", "A"]),
+    ("(?i)((((((((((a))))))))))\\10", "AA", Match, "AA", &'static ["A", "A", "A", "A", "A", "A", "A", "A", "A", "A"]),
     //#("(?i)((((((((((a))))))))))\\41", "AA", NoMatch, "", &'static []), NOT IMPLEMENTED
     //#("(?i)((((((((((a))))))))))\\41", "A!", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(((((((((a)))))))))", "A", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(?:(?:(?:(?:(?:(?:(?:(?:(?:(a))))))))))", "A", Match, "g1", "A"), NOT IMPLEMENTED
-    //("(?i)(?:(?:(?:(?:(?:(?:(?:(?:(?:(a|b|c))))))))))", "C", Match, "g1", "C"), NOT IMPLEMENTED
-    //("(?i)multiple words of text", "UH-UH", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?i)multiple words", "MULTIPLE WORDS, YEAH", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(.*)c(.*)", "ABCDE", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)\\((.*), (.*)\\)", "(A, B)", Match, "g2+"-"+g1", "B-A"), NOT IMPLEMENTED
-    //("(?i)[k]", "AB", NoMatch, "", &'static []), NOT IMPLEMENTED
+    ("(?i)(((((((((a)))))))))", "A", Match, "A", &'static ["A", "A", "A", "A", "A", "A", "A", "A", "A"]),
+    ("(?i)(?:(?:(?:(?:(?:(?:(?:(?:(?:(a))))))))))", "A", Match, "A", &'static ["A"]),
+    ("(?i)(?:(?:(?:(?:(?:(?:(?:(?:(?:(a|b|c))))))))))", "C", Match, "C", &'static ["C"]),
+    ("(?i)multiple words of text", "UH-UH", NoMatch, "", &'static []),
+    ("(?i)multiple words", "MULTIPLE WORDS, YEAH", Match, "MULTIPLE WORDS", &'static []),
+    ("(?i)(.*)c(.*)", "ABCDE", Match, "ABCDE", &'static ["AB", "DE"]),
+    ("(?i)\\((.*), (.*)\\)", "(A, B)", Match, "(A, B)", &'static ["A", "B"]),
+    ("(?i)[k]", "AB", NoMatch, "", &'static []),
 //#    ("(?i)abcd", "ABCD", Match, "", &'static []),
 //#    ("(?i)a(bc)d", "ABCD", Match, "g1+"-"+\\g1+"-"+\\\\g1", "BC-$1-\\BC"),
-    //("(?i)a[-]?c", "AC", Match, "", &'static []), NOT IMPLEMENTED
-    //("(?i)(abc)\\1", "ABCABC", Match, "g1", "ABC"), NOT IMPLEMENTED
-    //("(?i)([a-c]*)\\1", "ABCABC", Match, "g1", "ABC"), NOT IMPLEMENTED
-    //("a(?!b).", "abad", Match, "", &'static []), NOT IMPLEMENTED
-    //("a(?=d).", "abad", Match, "", &'static []), NOT IMPLEMENTED
-    //("a(?=c|d).", "abad", Match, "", &'static []), NOT IMPLEMENTED
+    ("(?i)a[-]?c", "AC", Match, "AC", &'static []),
+    ("(?i)(abc)\\1", "ABCABC", Match, "ABCABC", &'static ["ABC"]),
+    ("(?i)([a-c]*)\\1", "ABCABC", Match, "ABCABC", &'static ["ABC"]),
+    ("(?i)\u00e9", "\u00c9", Match, "\u00c9", &'static []),
+    ("(?i)\u00c9", "\u00e9", Match, "\u00e9", &'static []),
+    ("(?i)[\u00e0-\u00ff]+", "\u00c0\u00c9\u00de\u0178", Match, "\u00c0\u00c9\u00de\u0178", &'static []),
+    ("(?i)[\u00c0-\u00de]+", "\u00e0\u00e9\u00fe", Match, "\u00e0\u00e9\u00fe", &'static []),
+    ("(?i)\u03c2", "\u03a3\u03c3", Match, "\u03a3", &'static []),
+    ("(?i)(\u00e9)\\1", "\u00e9\u00c9", Match, "\u00e9\u00c9", &'static ["\u00e9"]),
+    ("(?i)s", "\u017f", NoMatch, "", &'static []), // Non-ASCII characters never match ASCII ones
+    ("(?i)\u017f", "S", NoMatch, "", &'static []),
+    ("(?i)[a-z]", "\u212a", NoMatch, "", &'static []),
+    ("a(?!b).", "abad", Match, "ad", &'static []),
+    ("a(?=d).", "abad", Match, "ad", &'static []),
+    ("a(?=c|d).", "abad", Match, "ad", &'static []),
     ("a(?:b|c|d)(.)", "ace", Match, "ace", &'static ["e"]),
     ("a(?:b|c|d)*(.)", "ace", Match, "ace", &'static ["e"]),
     ("a(?:b|c|d)+?(.)", "ace", Match, "ace", &'static ["e"]),
-    //("a(?:b|(c|e){1,2}?|d)+?(.)", "ace", Match, "ace", &'static ["c", "e"]), FAILS infinite loop
+    ("a(?:b|(c|e){1,2}?|d)+?(.)", "ace", Match, "ace", &'static ["c", "e"]),
     ("^(.+)?B", "AB", Match, "AB", &'static ["A"]),
 
     // lookbehind: split by : but not if it is escaped by -.
-    //("(?<!-):(.*?)(?<!-):", "a:bc-:de:f", Match, "g1", "bc-:de" ), NOT IMPLEMENTED
+    ("(?<!-):(.*?)(?<!-):", "a:bc-:de:f", Match, ":bc-:de:", &'static ["bc-:de"]),
     // escaping with \ as we know it
-    //("(?<!\\\):(.*?)(?<!\\\):", "a:bc\\:de:f", Match, "g1", "bc\\:de" ), NOT IMPLEMENTED
+    ("(?<!\\\\):(.*?)(?<!\\\\):", "a:bc\\:de:f", Match, ":bc\\:de:", &'static ["bc\\:de"]),
     // terminating with " and escaping with ? as in edifact
-    //("(?<!\\?)"(.*?)(?<!\\?)"", "a"bc?"de"f", Match, "g1", "bc?"de" ), NOT IMPLEMENTED
+    ("(?<!\\?)\"(.*?)(?<!\\?)\"", "a\"bc?\"de\"f", Match, "\"bc?\"de\"", &'static ["bc?\"de"]),
 
     // Comments using the (?#...) syntax
 
@@ -584,7 +779,7 @@ pub static TestCases: &'static [(&'static str, &'static str, TestResult, &'stati
     // Check odd placement of embedded pattern modifiers
 
     // not an error under PCRE/PRE:
-    //("w(?i)", "W", Match, "", &'static []), NOT IMPLEMENTED
+    ("w(?i)", "W", NoMatch, "", &'static []), // Flags only apply to what follows
     //# ("w(?i)", "W", ParseError),
 
     // Comments using the x embedded pattern modifier
@@ -607,10 +802,29 @@ xyz""", Match, "", &'static []),
 xyzabc
 123""", Match, "", &'static []),
 */
+    ("^abc", "jkl\nabc\nxyz", NoMatch, "", &'static []),
+    ("(?m)^abc", "jkl\nabc\nxyz", Match, "abc", &'static []),
+    ("(?m)abc$", "jkl\nxyzabc\n123", Match, "abc", &'static []),
+    ("(?m)^abc$", "jkl\r\nabc\r\nxyz", Match, "abc", &'static []),
+    ("(?m)^(\\d+)$", "a\u2028123\u2029b", Match, "123", &'static ["123"]),
+    ("(?m)$", "", Match, "", &'static []),
+    ("(?m)^$", "a\n\nb", Match, "", &'static []),
+    ("a(?m:$)\n^b", "a\nb", NoMatch, "", &'static []),
+    ("(?m:^b)", "a\nb", Match, "b", &'static []),
+    ("(?m)(?<=^a)b", "x\nab", Match, "b", &'static []),
     // using the s embedded pattern modifier
 
-    //("a.b", "a\nb", NoMatch, "", &'static []), NOT IMPLEMENTED
-    //("(?s)a.b", "a\nb", Match, "", &'static []), NOT IMPLEMENTED
+    ("a.b", "a\nb", NoMatch, "", &'static []),
+    ("(?s)a.b", "a\nb", Match, "a\nb", &'static []),
+    ("a.b", "a\rb", NoMatch, "", &'static []),
+    ("a.b", "a\u2028b", NoMatch, "", &'static []),
+    ("a.b", "a\u2029b", NoMatch, "", &'static []),
+    ("a.b", "a\u2027b", Match, "a\u2027b", &'static []),
+    ("a.*", "abc\ndef", Match, "abc", &'static []),
+    ("(?s)a.*", "abc\ndef", Match, "abc\ndef", &'static []),
+    ("(?s:a.)b.", "a\nb\n", NoMatch, "", &'static []),
+    ("a(?s).{4,5}b", "acc\nccb", Match, "acc\nccb", &'static []),
+    ("(?s)a(?-s).b", "a\nb", NoMatch, "", &'static []),
 
     // test \w, etc. both inside and outside character classes
 
@@ -640,7 +854,7 @@ xyzabc
     // bug 110866: reference to undefined group
     //(r"((.)\1+)", "", ParseError, "", &'static []), NOT IMPLEMENTED
     // bug 111869: search (PRE/PCRE NoMatchs on this one, SRE doesn"t)
-    //(r".*d", "abc\nabd", Match, "abd", &'static []), FAILS . matches newlines
+    (r".*d", "abc\nabd", Match, "abd", &'static []),
     // bug 112468: various expected syntax errors
     (r"(", "", ParseError, "", &'static []),
     //(r"[\41]", "!", Match, "", &'static []),
@@ -649,7 +863,7 @@ xyzabc
     // bug 115040: rescan if flags are modified inside pattern
     //(r" (?x)foo ", "foo", Match, "", &'static []), NOT IMPLEMENTED
     // bug 115618: negative lookahead
-    //(r"(?<!abc)(d.f)", "abcdefdof", Match, "", &'static []), NOT IMPLEMENTED
+    (r"(?<!abc)(d.f)", "abcdefdof", Match, "dof", &'static ["dof"]),
     // bug 116251: character class bug
     //(r"[\w-]+", "laser_beam", Match, "laser_beam", &'static []), FAILS not sure why it shouldn't?
     // bug 123769+127259: non-greedy backtracking bug
@@ -659,16 +873,16 @@ xyzabc
     // bug 127259: \Z shouldn"t depend on multiline mode
     //(r"(?ms).*?x\s*\Z(.*)","xx\nx\n", Match, "g1", ""), NOT IMPLEMENTED
     // bug 128899: uppercase literals under the ignorecase flag
-    //(r"(?i)M+", "MMM", Match, "", &'static []), NOT IMPLEMENTED
-    //(r"(?i)m+", "MMM", Match, "", &'static []), NOT IMPLEMENTED
-    //(r"(?i)[M]+", "MMM", Match, "", &'static []), NOT IMPLEMENTED
-    //(r"(?i)[m]+", "MMM", Match, "", &'static []), NOT IMPLEMENTED
+    (r"(?i)M+", "MMM", Match, "MMM", &'static []),
+    (r"(?i)m+", "MMM", Match, "MMM", &'static []),
+    (r"(?i)[M]+", "MMM", Match, "MMM", &'static []),
+    (r"(?i)[m]+", "MMM", Match, "MMM", &'static []),
     // bug 130748: ^* should be an error (nothing to repeat)
-    //(r"^*", "", ParseError, "", &'static []), FAILS infinite loop
+    (r"^*", "", ParseError, "", &'static []),
     // bug 133283: minimizing repeat problem
     //(r###"(?:"|[^"])*?"###, r###""\""###, Match, r###""\""###, &'static []), FAILS not sure what the original raw string should translate to
     // bug 477728: minimizing repeat problem
-    //(r"^.*?$", "one\ntwo\nthree\n", NoMatch, "", &'static []), FAILS . matches newline
+    (r"^.*?$", "one\ntwo\nthree\n", NoMatch, "", &'static []),
     // bug 483789: minimizing repeat problem
     (r"a[^>]*?b", "a>b", NoMatch, "", &'static []),
     // bug 490573: minimizing repeat problem